@@ -0,0 +1,187 @@
+use anyhow::{anyhow, ensure, Result};
+
+use crate::Vec2;
+
+/// What happens to agents that reach the edge of the arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Boundary {
+    /// Agents leaving through one edge come back through the opposite one.
+    Wrap,
+    /// Agents bounce off the edges elastically.
+    #[default]
+    Reflect,
+    /// Agents touching an edge are removed.
+    Absorb,
+}
+
+impl Boundary {
+    pub fn name(self) -> &'static str {
+        match self {
+            Boundary::Wrap => "wrap",
+            Boundary::Reflect => "reflect",
+            Boundary::Absorb => "absorb",
+        }
+    }
+}
+
+impl std::str::FromStr for Boundary {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "wrap" => Ok(Boundary::Wrap),
+            "reflect" => Ok(Boundary::Reflect),
+            "absorb" => Ok(Boundary::Absorb),
+            _ => Err(anyhow!("unknown boundary {s:?}")),
+        }
+    }
+}
+
+/// The rectangle `[0, width] x [0, height]` the agents live in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+    pub boundary: Boundary,
+}
+
+impl Arena {
+    pub fn new(width: f32, height: f32, boundary: Boundary) -> Self {
+        Self {
+            width,
+            height,
+            boundary,
+        }
+    }
+
+    /// Fails unless both sides are finite and positive, agents cannot be
+    /// confined to anything else.
+    pub fn check(&self) -> Result<()> {
+        ensure!(
+            [self.width, self.height]
+                .iter()
+                .all(|side| side.is_finite() && *side > 0.0),
+            "the arena must have a positive size, not {} x {}",
+            self.width,
+            self.height
+        );
+        Ok(())
+    }
+
+    /// Shortest vector going from `from` to `to`, which may cross the edges
+    /// of a wrapping arena.
+    pub fn displacement(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let d = to - from;
+        match self.boundary {
+            Boundary::Wrap => Vec2::new(wrap_delta(d.x, self.width), wrap_delta(d.y, self.height)),
+            _ => d,
+        }
+    }
+
+    /// Applies the boundary to a moved agent, returns `false` if it was absorbed.
+    pub fn confine(&self, position: &mut Vec2, velocity: &mut Vec2) -> bool {
+        match self.boundary {
+            Boundary::Wrap => {
+                position.x = wrap(position.x, self.width);
+                position.y = wrap(position.y, self.height);
+                true
+            }
+            Boundary::Reflect => {
+                bounce(&mut position.x, &mut velocity.x, self.width);
+                bounce(&mut position.y, &mut velocity.y, self.height);
+                true
+            }
+            Boundary::Absorb => {
+                (0.0..=self.width).contains(&position.x)
+                    && (0.0..=self.height).contains(&position.y)
+            }
+        }
+    }
+}
+
+fn wrap(x: f32, max: f32) -> f32 {
+    let x = x.rem_euclid(max);
+    // rem_euclid can round up to `max` for tiny negative inputs
+    if x >= max {
+        0.0
+    } else {
+        x
+    }
+}
+
+/// Shortest equivalent of the offset `d` on an axis that wraps every `max`.
+pub(crate) fn wrap_delta(d: f32, max: f32) -> f32 {
+    if d > max / 2.0 {
+        d - max
+    } else if d < -max / 2.0 {
+        d + max
+    } else {
+        d
+    }
+}
+
+fn bounce(position: &mut f32, velocity: &mut f32, max: f32) {
+    if *position < 0.0 {
+        *position = -*position;
+        *velocity = velocity.abs();
+    } else if *position > max {
+        *position = 2.0 * max - *position;
+        *velocity = -velocity.abs();
+    }
+
+    // an overshoot longer than the arena bounces off both edges, every
+    // `2 * max` of travel brings the agent back where it was going the same way
+    if !(0.0..=max).contains(position) {
+        let folded = wrap(*position, 2.0 * max);
+        if folded > max {
+            *position = 2.0 * max - folded;
+            *velocity = -*velocity;
+        } else {
+            *position = folded;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boundaries_confine_agents() {
+        let moved = Vec2::new(105.0, -3.0);
+        let heading = Vec2::new(10.0, -10.0);
+
+        let (mut position, mut velocity) = (moved, heading);
+        let arena = Arena::new(100.0, 50.0, Boundary::Wrap);
+        assert!(arena.confine(&mut position, &mut velocity));
+        assert_eq!((position, velocity), (Vec2::new(5.0, 47.0), heading));
+
+        let (mut position, mut velocity) = (moved, heading);
+        let arena = Arena::new(100.0, 50.0, Boundary::Reflect);
+        assert!(arena.confine(&mut position, &mut velocity));
+        assert_eq!(
+            (position, velocity),
+            (Vec2::new(95.0, 3.0), Vec2::new(-10.0, 10.0))
+        );
+
+        // far enough to bounce off both edges
+        let (mut position, mut velocity) = (Vec2::new(230.0, -120.0), heading);
+        assert!(arena.confine(&mut position, &mut velocity));
+        assert_eq!(
+            (position, velocity),
+            (Vec2::new(30.0, 20.0), Vec2::new(10.0, 10.0))
+        );
+
+        let (mut position, mut velocity) = (moved, heading);
+        let arena = Arena::new(100.0, 50.0, Boundary::Absorb);
+        assert!(!arena.confine(&mut position, &mut velocity));
+    }
+
+    #[test]
+    fn wrapped_displacement_takes_the_short_way() {
+        let arena = Arena::new(100.0, 50.0, Boundary::Wrap);
+        let d = arena.displacement(Vec2::new(2.0, 48.0), Vec2::new(97.0, 1.0));
+
+        assert_eq!(d, Vec2::new(-5.0, 3.0));
+    }
+}
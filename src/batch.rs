@@ -0,0 +1,439 @@
+use std::{
+    io::Write,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::{Context as _, Result};
+
+use crate::{Scenario, Species};
+
+/// Runs a scenario over many consecutive seeds on every core.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub scenario: Scenario,
+    /// Number of runs, run `i` uses the seed of the scenario plus `i`.
+    pub runs: u64,
+    pub max_steps: u64,
+    pub dt: f32,
+    /// Worker threads, 0 uses every available core.
+    pub threads: usize,
+}
+
+impl Batch {
+    pub fn new(scenario: Scenario) -> Self {
+        Self {
+            scenario,
+            runs: 1000,
+            max_steps: 100_000,
+            dt: 1.0 / 60.0,
+            threads: 0,
+        }
+    }
+
+    pub fn runs(mut self, runs: u64) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn dt(mut self, dt: f32) -> Self {
+        self.dt = dt;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Runs every seed, the report does not depend on the number of threads.
+    /// Fails if the scenario does not build.
+    pub fn run(&self) -> Result<BatchReport> {
+        let threads = match self.threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let next_run = AtomicU64::new(0);
+
+        let mut outcomes = std::thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| -> Result<Vec<RunOutcome>> {
+                        let mut outcomes = Vec::new();
+                        loop {
+                            let run = next_run.fetch_add(1, Ordering::Relaxed);
+                            if run >= self.runs {
+                                break Ok(outcomes);
+                            }
+                            outcomes.push(self.run_one(self.scenario.seed.wrapping_add(run))?);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("a batch worker panicked"))
+                .collect::<Result<Vec<_>>>()
+        })?
+        .concat();
+        outcomes.sort_by_key(|outcome| outcome.seed);
+
+        Ok(BatchReport {
+            species: self
+                .scenario
+                .ruleset
+                .species()
+                .map(|species| self.scenario.ruleset.name(species).to_owned())
+                .collect(),
+            outcomes,
+        })
+    }
+
+    /// Runs the scenario on a lattice if it sets one up, counting
+    /// generations as steps.
+    fn run_one(&self, seed: u64) -> Result<RunOutcome> {
+        let scenario = Scenario {
+            seed,
+            ..self.scenario.clone()
+        };
+        if let Some(mut lattice) = scenario.build_lattice()? {
+            let mut finish = None;
+            while finish.is_none() && lattice.generation() < self.max_steps {
+                finish = lattice.step();
+            }
+
+            return Ok(RunOutcome {
+                seed,
+                finished: finish.is_some(),
+                winner: finish.and_then(|finish| finish.winner),
+                steps: lattice.generation(),
+                elapsed: lattice.generation() as f64,
+                populations: lattice.populations(),
+            });
+        }
+
+        let mut world = scenario.build_world()?;
+
+        let mut finish = None;
+        while finish.is_none() && world.tick() < self.max_steps {
+            finish = world.step(self.dt);
+        }
+
+        Ok(RunOutcome {
+            seed,
+            finished: finish.is_some(),
+            winner: finish.and_then(|finish| finish.winner),
+            steps: world.tick(),
+            elapsed: world.elapsed(),
+            populations: world.populations(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOutcome {
+    pub seed: u64,
+    /// `false` when the run hit the step limit.
+    pub finished: bool,
+    pub winner: Option<Species>,
+    /// Steps, or generations of a lattice.
+    pub steps: u64,
+    /// Simulated seconds, or generations of a lattice.
+    pub elapsed: f64,
+    /// Final number of agents, or cells, of every species, indexed by species.
+    pub populations: Vec<usize>,
+}
+
+impl RunOutcome {
+    pub fn survivors(&self) -> usize {
+        self.populations.iter().sum()
+    }
+}
+
+/// One bar of a histogram, counting the values in `start..end`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bin {
+    pub start: f64,
+    pub end: f64,
+    pub count: usize,
+}
+
+/// Outcomes of a [`Batch`], sorted by seed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchReport {
+    pub species: Vec<String>,
+    pub outcomes: Vec<RunOutcome>,
+}
+
+impl BatchReport {
+    /// Fraction of the runs won by `species` and its 95% Wilson score interval.
+    pub fn win_probability(&self, species: Species) -> (f64, f64, f64) {
+        let wins = self
+            .outcomes
+            .iter()
+            .filter(|outcome| outcome.winner == Some(species))
+            .count();
+
+        wilson_interval(wins, self.outcomes.len())
+    }
+
+    /// Histogram of the simulated time the finished runs took.
+    pub fn finish_times(&self, bins: usize) -> Vec<Bin> {
+        let times = self
+            .outcomes
+            .iter()
+            .filter(|outcome| outcome.finished)
+            .map(|outcome| outcome.elapsed);
+
+        histogram(times, bins)
+    }
+
+    /// Histogram of the number of agents left at the end of the runs.
+    pub fn survivors(&self, bins: usize) -> Vec<Bin> {
+        histogram(
+            self.outcomes
+                .iter()
+                .map(|outcome| outcome.survivors() as f64),
+            bins,
+        )
+    }
+
+    pub fn write_summary<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let runs = self.outcomes.len();
+        let finished = self.outcomes.iter().filter(|o| o.finished).count();
+        let stalemates = self
+            .outcomes
+            .iter()
+            .filter(|o| o.finished && o.winner.is_none())
+            .count();
+        writeln!(
+            writer,
+            "runs: {runs} ({finished} finished, {stalemates} without a winner, {} hit the step limit)",
+            runs - finished
+        )?;
+
+        let width = self.species.iter().map(String::len).max().unwrap_or(0);
+        writeln!(writer, "\nwin probability, 95% confidence interval:")?;
+        for (i, name) in self.species.iter().enumerate() {
+            let (p, low, high) = self.win_probability(Species(i as u8));
+            writeln!(
+                writer,
+                "  {name:width$}  {:5.1}%  [{:5.1}%, {:5.1}%]",
+                p * 100.0,
+                low * 100.0,
+                high * 100.0
+            )?;
+        }
+
+        writeln!(writer, "\ntime to finish:")?;
+        write_histogram(&mut writer, &self.finish_times(10), "s")?;
+
+        writeln!(writer, "\nsurvivors at the end:")?;
+        write_histogram(&mut writer, &self.survivors(10), "")?;
+
+        writer.flush()
+    }
+
+    /// Writes one row per run, with the final population of every species.
+    pub fn write_csv<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        write!(writer, "seed,finished,winner,steps,time")?;
+        for name in &self.species {
+            write!(writer, ",{name}")?;
+        }
+        writeln!(writer)?;
+
+        for outcome in &self.outcomes {
+            let winner = outcome
+                .winner
+                .map_or("", |species| &self.species[species.index()]);
+            write!(
+                writer,
+                "{},{},{winner},{},{}",
+                outcome.seed, outcome.finished, outcome.steps, outcome.elapsed
+            )?;
+            for count in &outcome.populations {
+                write!(writer, ",{count}")?;
+            }
+            writeln!(writer)?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn save_csv<P>(&self, filepath: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        let file = std::fs::File::create(filepath)
+            .with_context(|| format!("Could not create {}", filepath.display()))?;
+
+        self.write_csv(std::io::BufWriter::new(file))
+            .with_context(|| format!("Could not write the report to {}", filepath.display()))
+    }
+}
+
+/// Proportion of successes and its 95% Wilson score interval, which stays
+/// meaningful for proportions close to 0 or 1.
+pub fn wilson_interval(successes: usize, trials: usize) -> (f64, f64, f64) {
+    if trials == 0 {
+        return (0.0, 0.0, 1.0);
+    }
+
+    const Z: f64 = 1.96;
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let denominator = 1.0 + Z * Z / n;
+    let center = (p + Z * Z / (2.0 * n)) / denominator;
+    let half_width = Z / denominator * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt();
+
+    (
+        p,
+        (center - half_width).max(0.0),
+        (center + half_width).min(1.0),
+    )
+}
+
+/// Splits the range of the values into `bins` bins of equal width, the last
+/// one includes the maximum.
+fn histogram<I>(values: I, bins: usize) -> Vec<Bin>
+where
+    I: Iterator<Item = f64> + Clone,
+{
+    let (min, max) = values
+        .clone()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+    if bins == 0 || min > max {
+        return Vec::new();
+    }
+
+    // a single bin holds every value when they are all equal
+    let bins = if min == max { 1 } else { bins };
+    let width = (max - min) / bins as f64;
+    let mut histogram = (0..bins)
+        .map(|i| Bin {
+            start: min + i as f64 * width,
+            end: if i + 1 == bins {
+                max
+            } else {
+                min + (i + 1) as f64 * width
+            },
+            count: 0,
+        })
+        .collect::<Vec<_>>();
+
+    for value in values {
+        let i = if width > 0.0 {
+            (((value - min) / width) as usize).min(bins - 1)
+        } else {
+            0
+        };
+        histogram[i].count += 1;
+    }
+
+    histogram
+}
+
+fn write_histogram<W>(writer: &mut W, bins: &[Bin], unit: &str) -> std::io::Result<()>
+where
+    W: Write,
+{
+    const BAR_WIDTH: usize = 40;
+
+    if bins.is_empty() {
+        return writeln!(writer, "  no data");
+    }
+
+    let highest = bins.iter().map(|bin| bin.count).max().unwrap_or(0).max(1);
+    for bin in bins {
+        let bar = "#".repeat(bin.count * BAR_WIDTH / highest);
+        let line = format!(
+            "  {:>9.1}{unit} - {:>9.1}{unit}  {:>6}  {bar}",
+            bin.start, bin.end, bin.count
+        );
+        writeln!(writer, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LatticeScenario, Ruleset};
+
+    #[test]
+    fn wilson_interval_matches_reference_values() {
+        let (p, low, high) = wilson_interval(50, 100);
+        assert_eq!(p, 0.5);
+        assert!((low - 0.4038).abs() < 1e-4);
+        assert!((high - 0.5962).abs() < 1e-4);
+
+        let (_, low, high) = wilson_interval(0, 10);
+        assert_eq!(low, 0.0);
+        assert!((high - 0.2775).abs() < 1e-4);
+    }
+
+    #[test]
+    fn reports_do_not_depend_on_the_thread_count() {
+        let batch = Batch::new(Scenario::from_ruleset(Ruleset::classic(), 8))
+            .runs(8)
+            .max_steps(1_000);
+
+        let report = batch.clone().threads(1).run().unwrap();
+        assert_eq!(report, batch.threads(4).run().unwrap());
+        assert_eq!(report.outcomes.len(), 8);
+
+        let total = report
+            .species
+            .iter()
+            .enumerate()
+            .map(|(i, _)| report.win_probability(Species(i as u8)).0);
+        let finished_with_winner = report
+            .outcomes
+            .iter()
+            .filter(|o| o.winner.is_some())
+            .count();
+        assert!((total.sum::<f64>() - finished_with_winner as f64 / 8.0).abs() < 1e-9);
+
+        let finished = report.outcomes.iter().filter(|o| o.finished).count();
+        let binned = report
+            .finish_times(5)
+            .iter()
+            .map(|bin| bin.count)
+            .sum::<usize>();
+        assert_eq!(binned, finished);
+        assert_eq!(report.survivors(3)[0].count, 8);
+    }
+
+    #[test]
+    fn lattice_scenarios_run_on_the_lattice() {
+        let mut scenario = Scenario::from_ruleset(Ruleset::classic(), 50);
+        scenario.lattice = Some(LatticeScenario {
+            width: 20,
+            height: 20,
+            ..LatticeScenario::default()
+        });
+        let report = Batch::new(scenario).runs(2).max_steps(5).run().unwrap();
+
+        for outcome in &report.outcomes {
+            assert_eq!(outcome.steps, 5);
+            assert_eq!(outcome.elapsed, 5.0);
+            assert!(outcome.survivors() <= 400);
+        }
+    }
+}
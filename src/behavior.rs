@@ -0,0 +1,209 @@
+use std::fmt::Debug;
+
+use crate::{Agent, FightResults, Rad, Rng, Ruleset, Species, Steering, Vec2};
+
+/// Decides where an agent goes on every step.
+///
+/// Behaviours are shared by every agent using them and may be called from
+/// any thread, those keeping state need interior mutability. Random
+/// decisions should be drawn from `rng` so runs stay reproducible.
+pub trait Behavior: Debug + Send + Sync {
+    /// New velocity of the agent seeing `view`.
+    fn steer(&self, view: &Neighbourhood, rng: &mut Rng) -> Vec2;
+}
+
+/// Handle of a behaviour added to a world with
+/// [`World::add_behavior`](crate::World::add_behavior).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BehaviorId(pub(crate) u32);
+
+impl BehaviorId {
+    /// [`ChaseFlee`], which every world starts with.
+    pub const CHASE_FLEE: Self = Self(0);
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Another agent within the sensing radius of the one deciding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbour {
+    /// Index into [`World::agents`](crate::World::agents).
+    pub index: usize,
+    /// From the deciding agent to this one, across the edges of a wrapping
+    /// arena when that is shorter.
+    pub offset: Vec2,
+    pub velocity: Vec2,
+    pub species: Species,
+    /// Outcome of the deciding agent fighting this one.
+    pub result: FightResults,
+}
+
+/// Read-only view of the surroundings of an agent, gathered from the
+/// spatial index.
+#[derive(Debug, Clone, Copy)]
+pub struct Neighbourhood<'a> {
+    pub index: usize,
+    pub agent: &'a Agent,
+    /// Steering parameters of the species of the agent.
+    pub steering: &'a Steering,
+    pub ruleset: &'a Ruleset,
+    /// Agents within the sensing radius, without those hidden behind an
+    /// obstacle when the world checks the line of sight.
+    pub neighbours: &'a [Neighbour],
+    /// Offset to the closest point of the nearest obstacle within the
+    /// sensing radius.
+    pub obstacle: Option<Vec2>,
+    pub dt: f32,
+}
+
+impl Neighbourhood<'_> {
+    /// Closest neighbour the agent gets `result` against, the first one
+    /// found on ties.
+    pub fn nearest(&self, result: FightResults) -> Option<&Neighbour> {
+        self.neighbours
+            .iter()
+            .filter(|n| n.result == result)
+            .min_by(|a, b| {
+                a.offset
+                    .length_squared()
+                    .total_cmp(&b.offset.length_squared())
+            })
+    }
+
+    /// Heading of the agent at its maximum speed, a random one when it
+    /// stands still.
+    fn cruise(&self, rng: &mut Rng) -> Vec2 {
+        let mut heading = self.agent.velocity.normalize_or_zero();
+        if heading == Vec2::ZERO {
+            heading = rng.unit_vector();
+        }
+        heading * self.steering.max_speed
+    }
+
+    /// Turns `velocity` toward `direction`, and away from the obstacle.
+    fn head(&self, velocity: Vec2, direction: Vec2) -> Vec2 {
+        let mut desired = direction.normalize_or_zero();
+        if let Some(offset) = self.obstacle {
+            desired += self.steering.avoidance(offset);
+        }
+        self.steering.turn(velocity, desired, self.dt)
+    }
+}
+
+/// Turns toward the nearest prey and away from the nearest predator, the
+/// behaviour of every species unless told otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChaseFlee;
+
+impl Behavior for ChaseFlee {
+    fn steer(&self, view: &Neighbourhood, _rng: &mut Rng) -> Vec2 {
+        let prey = view.nearest(FightResults::Won).map(|n| n.offset);
+        let predator = view.nearest(FightResults::Lost).map(|n| n.offset);
+
+        view.steering
+            .steer(view.agent.velocity, prey, predator, view.obstacle, view.dt)
+    }
+}
+
+/// Ignores everybody and keeps changing heading at random.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RandomWalk {
+    /// Standard deviation of the heading after one second, in radians.
+    pub turn: f32,
+}
+
+impl Default for RandomWalk {
+    fn default() -> Self {
+        Self { turn: 2.0 }
+    }
+}
+
+impl Behavior for RandomWalk {
+    fn steer(&self, view: &Neighbourhood, rng: &mut Rng) -> Vec2 {
+        let velocity = view.cruise(rng);
+        let angle = rng.normal() * self.turn * view.dt.sqrt();
+        view.head(velocity, velocity.rotate(Rad(angle)))
+    }
+}
+
+/// Ignores everybody and drifts smoothly, heading for a random point of a
+/// circle held in front of the agent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wander {
+    /// Distance from the agent to the center of the circle.
+    pub distance: f32,
+    pub radius: f32,
+}
+
+impl Default for Wander {
+    fn default() -> Self {
+        Self {
+            distance: 40.0,
+            radius: 20.0,
+        }
+    }
+}
+
+impl Behavior for Wander {
+    fn steer(&self, view: &Neighbourhood, rng: &mut Rng) -> Vec2 {
+        let velocity = view.cruise(rng);
+        let target = velocity.normalize_or_zero() * self.distance + rng.unit_vector() * self.radius;
+        view.head(velocity, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_behaviors_keep_moving() {
+        let ruleset = Ruleset::classic();
+        let steering = Steering::default();
+        let agent = Agent::new([50.0, 50.0], [10.0, 0.0], Species(0));
+        let neighbours = [
+            Neighbour {
+                index: 1,
+                offset: Vec2::new(0.0, 30.0),
+                velocity: Vec2::ZERO,
+                species: Species(2),
+                result: FightResults::Won,
+            },
+            Neighbour {
+                index: 2,
+                offset: Vec2::new(0.0, 20.0),
+                velocity: Vec2::ZERO,
+                species: Species(2),
+                result: FightResults::Won,
+            },
+        ];
+        let view = Neighbourhood {
+            index: 0,
+            agent: &agent,
+            steering: &steering,
+            ruleset: &ruleset,
+            neighbours: &neighbours,
+            obstacle: None,
+            dt: 0.1,
+        };
+        let mut rng = Rng::new(1);
+
+        assert_eq!(view.nearest(FightResults::Won).unwrap().index, 2);
+        assert!(view.nearest(FightResults::Lost).is_none());
+        assert!(ChaseFlee.steer(&view, &mut rng).y > 0.0);
+
+        // wandering agents pick up their maximum speed and turn at most
+        // as fast as their steering allows
+        let behaviors: [&dyn Behavior; 2] = [&RandomWalk::default(), &Wander::default()];
+        for behavior in behaviors {
+            for _ in 0..20 {
+                let velocity = behavior.steer(&view, &mut rng);
+                assert!((velocity.length() - steering.max_speed).abs() < 1e-3);
+                let angle = Vec2::new(1.0, 0.0).angle_to(velocity).0.abs();
+                assert!(angle <= steering.max_turn_rate * view.dt + 1e-4);
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Runs the simulation without a window or a GPU and reports the outcome.
+//!
+//! ```text
+//! rps_headless [--scenario FILE] [--seed N] [--max-steps N] [--dt SECONDS]
+//!              [--ruleset classic|rpsls|balanced-N] [--population N]
+//!              [--csv FILE] [--stats-interval TICKS] [--mean-field FILE]
+//!              [--runs N] [--threads N] [--report FILE]
+//!              [--sweep PARAMETER=START:END:STEPS]...
+//! rps_headless --verify-replay FILE
+//! ```
+//!
+//! `--ruleset` and `--population` set up a scenario when no file is given.
+//! `--csv` writes the population time series sampled every `--stats-interval`
+//! ticks. `--mean-field` writes the populations the mean-field equations
+//! predict at the same times and prints how far the run strayed from them.
+//!
+//! With `--runs` the scenario is run over that many consecutive seeds on
+//! `--threads` threads, every core by default, and a summary of the outcomes
+//! is printed. `--report` writes the outcome of every run as CSV.
+//!
+//! `--sweep`, given once or twice, runs such a batch for every combination of
+//! parameter values and writes a CSV table of the outcomes to `--report`, or
+//! to the standard output.
+//!
+//! Scenarios setting up a lattice run on it for at most `--max-steps`
+//! generations, `--csv` and the batch options only apply to worlds.
+//!
+//! `--verify-replay` plays a replay back and fails unless it ends with
+//! the recorded checksum.
+
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Context as _, Result};
+use rps_simulator::*;
+
+#[derive(Debug)]
+struct Options {
+    scenario: Option<PathBuf>,
+    seed: Option<u64>,
+    ruleset: Ruleset,
+    population: usize,
+    max_steps: u64,
+    dt: f32,
+    csv: Option<PathBuf>,
+    mean_field: Option<PathBuf>,
+    stats_interval: u64,
+    verify_replay: Option<PathBuf>,
+    runs: Option<u64>,
+    threads: usize,
+    report: Option<PathBuf>,
+    sweep: Vec<SweepAxis>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            scenario: None,
+            seed: None,
+            ruleset: Ruleset::classic(),
+            population: 30,
+            max_steps: 100_000,
+            dt: 1.0 / 60.0,
+            csv: None,
+            mean_field: None,
+            stats_interval: 60,
+            verify_replay: None,
+            runs: None,
+            threads: 0,
+            report: None,
+            sweep: Vec::new(),
+        }
+    }
+}
+
+impl Options {
+    fn from_args() -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or_else(|| anyhow!("{flag} expects a value"));
+
+            match flag.as_str() {
+                "--scenario" => options.scenario = Some(value()?.into()),
+                "--seed" => options.seed = Some(parse(&flag, &value()?)?),
+                "--ruleset" => options.ruleset = Ruleset::preset(&value()?)?,
+                "--population" => options.population = parse(&flag, &value()?)?,
+                "--max-steps" => options.max_steps = parse(&flag, &value()?)?,
+                "--dt" => options.dt = parse(&flag, &value()?)?,
+                "--csv" => options.csv = Some(value()?.into()),
+                "--mean-field" => options.mean_field = Some(value()?.into()),
+                "--verify-replay" => options.verify_replay = Some(value()?.into()),
+                "--runs" => options.runs = Some(parse(&flag, &value()?)?),
+                "--threads" => options.threads = parse(&flag, &value()?)?,
+                "--report" => options.report = Some(value()?.into()),
+                "--sweep" => options.sweep.push(value()?.parse()?),
+                "--stats-interval" => options.stats_interval = parse(&flag, &value()?)?,
+                _ => return Err(anyhow!("unknown argument {flag}")),
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn scenario(&self) -> Result<Scenario> {
+        let mut scenario = match &self.scenario {
+            Some(filepath) => Scenario::load(filepath)?,
+            None => Scenario::from_ruleset(self.ruleset.clone(), self.population),
+        };
+        if let Some(seed) = self.seed {
+            scenario.seed = seed;
+        }
+
+        Ok(scenario)
+    }
+}
+
+fn parse<T>(flag: &str, value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .parse()
+        .with_context(|| format!("invalid value {value:?} for {flag}"))
+}
+
+fn main() -> Result<()> {
+    let options = Options::from_args()?;
+
+    if let Some(filepath) = &options.verify_replay {
+        let world = Replay::load(filepath)?.verify()?;
+        println!("replay verified, checksum {:016x}", world.checksum());
+        return Ok(());
+    }
+
+    let batch = Batch::new(options.scenario()?)
+        .runs(options.runs.unwrap_or(100))
+        .max_steps(options.max_steps)
+        .dt(options.dt)
+        .threads(options.threads);
+
+    if !options.sweep.is_empty() {
+        let mut sweep = Sweep::new(batch);
+        for axis in &options.sweep {
+            sweep = sweep.axis(axis.clone());
+        }
+
+        let report = sweep.run()?;
+        match &options.report {
+            Some(filepath) => report.save_csv(filepath)?,
+            None => report.write_csv(std::io::stdout().lock())?,
+        }
+        return Ok(());
+    }
+
+    if options.runs.is_some() {
+        let report = batch.run()?;
+
+        report.write_summary(std::io::stdout().lock())?;
+        if let Some(filepath) = &options.report {
+            report.save_csv(filepath)?;
+        }
+        return Ok(());
+    }
+
+    let scenario = options.scenario()?;
+    if let Some(mut lattice) = scenario.build_lattice()? {
+        let mut finish = None;
+        while finish.is_none() && lattice.generation() < options.max_steps {
+            finish = lattice.step();
+        }
+
+        print_winner(lattice.ruleset(), finish);
+        println!("generations: {}", lattice.generation());
+        print_populations(lattice.ruleset(), &lattice.populations());
+        return Ok(());
+    }
+
+    let mut builder = scenario.world_builder();
+    if options.csv.is_some() || options.mean_field.is_some() {
+        builder = builder.statistics(options.stats_interval);
+    }
+    let mut world = builder.build()?;
+
+    let mut finish = None;
+    while finish.is_none() && world.tick() < options.max_steps {
+        finish = world.step(options.dt);
+    }
+
+    print_winner(world.ruleset(), finish);
+    println!("steps: {}", world.tick());
+    println!("time: {:.2}s", world.elapsed());
+    print_populations(world.ruleset(), &world.populations());
+
+    if let (Some(filepath), Some(statistics)) = (&options.csv, world.statistics()) {
+        statistics.save_csv(filepath)?;
+    }
+    if let (Some(filepath), Some(statistics)) = (&options.mean_field, world.statistics()) {
+        let prediction = predict(&world, statistics);
+        println!(
+            "mean-field deviation: {:.4}",
+            prediction.deviation(statistics)
+        );
+        prediction.save_csv(filepath)?;
+    }
+
+    Ok(())
+}
+
+/// Mean-field prediction of the run, meeting others at the rate of agents
+/// moving at the mean speed recorded in the first sample.
+fn predict(world: &World, statistics: &Statistics) -> Prediction {
+    let (width, height) = world.size();
+    let (speed, count) = statistics
+        .samples()
+        .first()
+        .map(|sample| (sample.mean_speed, sample.populations.iter().sum::<usize>()))
+        .unwrap_or_default();
+    let rate = MeanField::encounter_rate(
+        world.contact_radius() as f64,
+        speed as f64,
+        count as f64 / (width as f64 * height as f64),
+    );
+
+    MeanField::from_fight(world.ruleset(), world.fight())
+        .rate(rate)
+        .predict(statistics)
+}
+
+fn print_winner(ruleset: &Ruleset, finish: Option<Finish>) {
+    match finish {
+        Some(Finish {
+            winner: Some(species),
+            ..
+        }) => println!("winner: {}", ruleset.name(species)),
+        Some(_) => println!("winner: none (stalemate)"),
+        None => println!("winner: none (step limit reached)"),
+    }
+}
+
+fn print_populations(ruleset: &Ruleset, populations: &[usize]) {
+    for (species, count) in ruleset.species().zip(populations) {
+        println!("{}: {count}", ruleset.name(species));
+    }
+}
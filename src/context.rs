@@ -1,4 +1,4 @@
-use std::{fmt::Debug, path::Path};
+use std::{fmt::Debug, path::Path, time::Instant};
 
 use anyhow::Result;
 use winit::{
@@ -7,13 +7,15 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
-use crate::renderer::*;
+use crate::{renderer::*, Vec2};
 
 #[derive(Debug, Clone)]
 pub struct ContextBuilder {
     pub title: String,
     pub size: winit::dpi::PhysicalSize<u32>,
     pub backends: wgpu::Backends,
+    /// Number of [`MainLoop::update`] calls per second at 1x speed.
+    pub tick_rate: f32,
 }
 
 impl ContextBuilder {
@@ -39,6 +41,11 @@ impl ContextBuilder {
         self
     }
 
+    pub fn tick_rate(mut self, tick_rate: f32) -> Self {
+        self.tick_rate = tick_rate;
+        self
+    }
+
     pub fn build(self) -> Result<(Context, EventLoop<()>)> {
         let event_loop = EventLoop::new();
 
@@ -55,6 +62,10 @@ impl ContextBuilder {
                 renderer,
                 window,
                 close_requested: false,
+                time_step: 1.0 / self.tick_rate,
+                speed: 1.0,
+                accumulator: 0.0,
+                last_frame: Instant::now(),
             },
             event_loop,
         ))
@@ -67,6 +78,7 @@ impl Default for ContextBuilder {
             title: "no title".into(),
             backends: wgpu::Backends::all(),
             size: winit::dpi::PhysicalSize::new(640, 480),
+            tick_rate: 60.0,
         }
     }
 }
@@ -76,13 +88,54 @@ pub struct Context {
     renderer: Renderer,
     window: Window,
     close_requested: bool,
+    time_step: f32,
+    speed: f32,
+    /// Scaled time not simulated yet, always less than a time step after updating.
+    accumulator: f32,
+    last_frame: Instant,
 }
 
 impl Context {
+    pub const MIN_SPEED: f32 = 0.25;
+    pub const MAX_SPEED: f32 = 64.0;
+
+    /// Longest frame accounted for, so a stall does not trigger a burst of
+    /// updates the loop can never catch up with.
+    const MAX_FRAME_TIME: f32 = 0.25;
+
     pub fn close(&mut self) {
         self.close_requested = true;
     }
 
+    /// Duration of one update in seconds, the `dt` given to [`MainLoop::update`].
+    pub fn time_step(&self) -> f32 {
+        self.time_step
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Runs more or fewer fixed updates per second, clamped between
+    /// [`Context::MIN_SPEED`] and [`Context::MAX_SPEED`].
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(Self::MIN_SPEED, Self::MAX_SPEED);
+    }
+
+    /// Number of updates due since the last frame, scaled by the speed.
+    fn pending_updates(&mut self) -> u32 {
+        let now = Instant::now();
+        let frame_time = (now - self.last_frame)
+            .as_secs_f32()
+            .min(Self::MAX_FRAME_TIME);
+        self.last_frame = now;
+
+        self.accumulator += frame_time * self.speed;
+        let updates = (self.accumulator / self.time_step) as u32;
+        self.accumulator -= updates as f32 * self.time_step;
+        updates
+    }
+
     pub fn run<F, A>(mut self, event_loop: EventLoop<()>, app_init: F) -> !
     where
         A: MainLoop + 'static,
@@ -90,6 +143,7 @@ impl Context {
     {
         let mut app = app_init(&mut self);
         self.window.set_visible(true);
+        self.last_frame = Instant::now();
 
         event_loop.run(move |event, _, control_flow| match event {
             Event::WindowEvent {
@@ -108,7 +162,8 @@ impl Context {
             },
 
             Event::RedrawRequested(id) if id == self.window.id() => {
-                let draw_command = app.draw(&mut self);
+                let alpha = self.accumulator / self.time_step;
+                let draw_command = app.draw(&mut self, alpha);
                 match self.renderer.draw(&draw_command) {
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
@@ -118,7 +173,10 @@ impl Context {
             }
 
             Event::MainEventsCleared => {
-                app.update(&mut self);
+                for _ in 0..self.pending_updates() {
+                    let dt = self.time_step;
+                    app.update(&mut self, dt);
+                }
 
                 if self.close_requested {
                     *control_flow = ControlFlow::Exit;
@@ -130,10 +188,46 @@ impl Context {
         });
     }
 
+    /// Fits the `[0, width] x [0, height]` rectangle to the window whatever
+    /// its size, instead of drawing in window pixels.
+    pub fn set_view(&mut self, width: f32, height: f32) {
+        self.renderer.set_view(Some((width, height)));
+    }
+
+    /// Position under the cursor in the coordinates set by [`Context::set_view`],
+    /// from a position reported by a window event.
+    pub fn view_position(&self, position: winit::dpi::PhysicalPosition<f64>) -> Vec2 {
+        self.renderer
+            .unproject(position.x as f32, position.y as f32)
+            .into()
+    }
+
     pub fn create_mesh(&self, width: f32, height: f32) -> Mesh {
         Mesh::rect(&self.renderer.device, width, height)
     }
 
+    /// Circle of the given radius, see [`Mesh::circle`].
+    pub fn create_circle_mesh(&self, radius: f32, segments: u16) -> Mesh {
+        Mesh::circle(&self.renderer.device, radius, segments)
+    }
+
+    pub fn create_texture(
+        &self,
+        image: &image::DynamicImage,
+        grid_width: u32,
+        grid_height: u32,
+    ) -> anyhow::Result<Texture> {
+        let (texture, _) = Texture::from_image(
+            &self.renderer.device,
+            &self.renderer.queue,
+            image,
+            grid_width,
+            grid_height,
+        )?;
+
+        Ok(texture)
+    }
+
     pub fn load_texture_atlas<P>(
         &self,
         filepath: P,
@@ -164,11 +258,15 @@ impl Context {
 }
 
 pub trait MainLoop {
-    fn update(&mut self, _ctx: &mut Context) {}
+    /// Called at a fixed rate, `dt` is [`Context::time_step`] whatever the
+    /// frame rate and speed are.
+    fn update(&mut self, _ctx: &mut Context, _dt: f32) {}
 
     fn event(&mut self, _ctx: &mut Context, _event: &WindowEvent) {}
 
-    fn draw(&mut self, _ctx: &mut Context) -> DrawCommand {
+    /// `alpha` in `[0, 1)` is how far the frame is between the last update
+    /// and the next one, for interpolating what is drawn.
+    fn draw(&mut self, _ctx: &mut Context, _alpha: f32) -> DrawCommand<'_> {
         DrawCommand::Clear(wgpu::Color::BLACK)
     }
 }
@@ -0,0 +1,27 @@
+/// Birth and death on top of the fights: winners eat the losers instead of
+/// converting them, and agents live on the energy they get from their prey.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ecology {
+    /// Energy of the agents spawned with the world.
+    pub initial_energy: f32,
+    /// Energy gained by eating an agent.
+    pub prey_energy: f32,
+    /// Energy lost per second, agents starve once they have none left.
+    pub metabolism: f32,
+    /// Energy at which an agent splits in two, the offspring taking half.
+    pub reproduction_threshold: f32,
+    /// Total population above which nobody reproduces.
+    pub carrying_capacity: usize,
+}
+
+impl Default for Ecology {
+    fn default() -> Self {
+        Self {
+            initial_energy: 1.0,
+            prey_energy: 1.0,
+            metabolism: 0.1,
+            reproduction_threshold: 2.0,
+            carrying_capacity: 1000,
+        }
+    }
+}
@@ -0,0 +1,540 @@
+//! Little endian binary encoding shared by snapshots and replays.
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::{
+    Agent, Boundary, Combat, Ecology, FightModel, FightResults, FightTiming, LatticeRates,
+    LatticeScenario, LatticeSchedule, Mutation, MutationMatrix, Obstacle, Rng, Ruleset, SpawnMask,
+    SpawnPattern, Species, SpeciesInfo, Stalemate, Steering, TieBreak, Vec2, WinMatrix,
+};
+
+/// Little endian encoder.
+pub(crate) struct Writer(pub(crate) Vec<u8>);
+
+impl Writer {
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn f64(&mut self, value: f64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    pub(crate) fn option<T, F>(&mut self, value: Option<T>, write: F)
+    where
+        F: FnOnce(&mut Self, T),
+    {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                write(self, value);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    pub(crate) fn vec2(&mut self, value: Vec2) {
+        self.f32(value.x);
+        self.f32(value.y);
+    }
+
+    pub(crate) fn rng(&mut self, rng: &Rng) {
+        for word in rng.state() {
+            self.u64(word);
+        }
+    }
+
+    pub(crate) fn steering(&mut self, steering: &Steering) {
+        self.f32(steering.chase_weight);
+        self.f32(steering.flee_weight);
+        self.f32(steering.max_speed);
+        self.f32(steering.max_turn_rate);
+        self.f32(steering.sensing_radius);
+        self.f32(steering.avoid_weight);
+    }
+
+    pub(crate) fn obstacles(&mut self, obstacles: &[Obstacle]) {
+        self.u32(obstacles.len() as u32);
+        for obstacle in obstacles {
+            match *obstacle {
+                Obstacle::Rect { min, max } => {
+                    self.u8(0);
+                    self.vec2(min);
+                    self.vec2(max);
+                }
+                Obstacle::Circle { center, radius } => {
+                    self.u8(1);
+                    self.vec2(center);
+                    self.f32(radius);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn spawn_pattern(&mut self, pattern: &SpawnPattern) {
+        match pattern {
+            SpawnPattern::Uniform => self.u8(0),
+            SpawnPattern::PoissonDisk { min_distance } => {
+                self.u8(1);
+                self.f32(*min_distance);
+            }
+            SpawnPattern::Clusters { spread } => {
+                self.u8(2);
+                self.f32(*spread);
+            }
+            SpawnPattern::Rings => self.u8(3),
+            SpawnPattern::Stripes => self.u8(4),
+            SpawnPattern::Mask(mask) => {
+                self.u8(5);
+                self.u32(mask.image.width());
+                self.u32(mask.image.height());
+                self.0.extend_from_slice(mask.image.as_raw());
+                self.u32(mask.colors.len() as u32);
+                for (color, species) in &mask.colors {
+                    self.0.extend_from_slice(color);
+                    self.u8(species.0);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn fight(&mut self, fight: &FightModel) {
+        self.option(fight.odds.as_ref(), |w, odds| {
+            w.u8(match odds.timing {
+                FightTiming::PerContact => 0,
+                FightTiming::PerSecond => 1,
+            });
+            w.u32(odds.len() as u32);
+            for a in 0..odds.len() {
+                for b in 0..odds.len() {
+                    w.f32(odds.get(Species(a as u8), Species(b as u8)));
+                }
+            }
+        });
+        self.u8(match fight.ties {
+            TieBreak::Nothing => 0,
+            TieBreak::PushApart => 1,
+            TieBreak::RandomConversion => 2,
+        });
+        self.option(fight.combat, |w, combat| {
+            w.f32(combat.damage_won);
+            w.f32(combat.damage_tied);
+            w.f32(combat.damage_lost);
+            w.f32(combat.cooldown);
+            w.f32(combat.healing);
+        });
+    }
+
+    pub(crate) fn mutation(&mut self, mutation: &Mutation) {
+        self.f32(mutation.rate);
+        self.option(mutation.matrix.as_ref(), |w, matrix| {
+            w.u32(matrix.len() as u32);
+            for a in 0..matrix.len() {
+                for b in 0..matrix.len() {
+                    w.f32(matrix.get(Species(a as u8), Species(b as u8)));
+                }
+            }
+        });
+    }
+
+    pub(crate) fn ecology(&mut self, ecology: Option<&Ecology>) {
+        self.option(ecology, |w, ecology| {
+            w.f32(ecology.initial_energy);
+            w.f32(ecology.prey_energy);
+            w.f32(ecology.metabolism);
+            w.f32(ecology.reproduction_threshold);
+            w.u64(ecology.carrying_capacity as u64);
+        });
+    }
+
+    pub(crate) fn lattice(&mut self, lattice: Option<&LatticeScenario>) {
+        self.option(lattice, |w, lattice| {
+            w.u32(lattice.width as u32);
+            w.u32(lattice.height as u32);
+            w.f32(lattice.rates.selection);
+            w.f32(lattice.rates.reproduction);
+            w.f32(lattice.rates.exchange);
+            w.u8(match lattice.schedule {
+                LatticeSchedule::Asynchronous => 0,
+                LatticeSchedule::Synchronous => 1,
+            });
+        });
+    }
+
+    pub(crate) fn pairs(&mut self, pairs: &[(usize, usize)]) {
+        self.u32(pairs.len() as u32);
+        for &(i, j) in pairs {
+            self.u32(i as u32);
+            self.u32(j as u32);
+        }
+    }
+
+    pub(crate) fn boundary(&mut self, boundary: Boundary) {
+        self.u8(match boundary {
+            Boundary::Wrap => 0,
+            Boundary::Reflect => 1,
+            Boundary::Absorb => 2,
+        });
+    }
+
+    pub(crate) fn stalemate(&mut self, stalemate: &Stalemate) {
+        self.option(stalemate.time_limit, Self::f64);
+        self.option(stalemate.quiet_period, Self::f64);
+    }
+
+    pub(crate) fn ruleset(&mut self, ruleset: &Ruleset) {
+        self.u32(ruleset.len() as u32);
+        for species in ruleset.species() {
+            let info = ruleset.info(species);
+            self.string(&info.name);
+            self.u32(info.atlas_cell[0]);
+            self.u32(info.atlas_cell[1]);
+            self.0.extend_from_slice(&info.tint);
+        }
+        for a in ruleset.species() {
+            for b in ruleset.species() {
+                self.u8(match ruleset.fight(a, b) {
+                    FightResults::Won => 0,
+                    FightResults::Tied => 1,
+                    FightResults::Lost => 2,
+                });
+            }
+        }
+    }
+
+    pub(crate) fn agents(&mut self, agents: &[Agent]) {
+        self.u32(agents.len() as u32);
+        for agent in agents {
+            self.agent(agent);
+        }
+    }
+
+    pub(crate) fn agent(&mut self, agent: &Agent) {
+        self.vec2(agent.position);
+        self.vec2(agent.velocity);
+        self.u8(agent.species.0);
+        self.f32(agent.health);
+        self.f32(agent.cooldown);
+        self.f32(agent.energy);
+    }
+}
+
+/// Little endian decoder, every read fails once the input runs out.
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.0.len() >= len, "unexpected end of data");
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        Ok(std::str::from_utf8(self.take(len)?)?.to_owned())
+    }
+
+    pub(crate) fn option<T, F>(&mut self, read: F) -> Result<Option<T>>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(read(self)?)),
+            n => bail!("invalid option tag {n}"),
+        }
+    }
+
+    pub(crate) fn vec2(&mut self) -> Result<Vec2> {
+        Ok(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    pub(crate) fn rng(&mut self) -> Result<Rng> {
+        Ok(Rng::from_state([
+            self.u64()?,
+            self.u64()?,
+            self.u64()?,
+            self.u64()?,
+        ]))
+    }
+
+    pub(crate) fn steering(&mut self) -> Result<Steering> {
+        Ok(Steering {
+            chase_weight: self.f32()?,
+            flee_weight: self.f32()?,
+            max_speed: self.f32()?,
+            max_turn_rate: self.f32()?,
+            sensing_radius: self.f32()?,
+            avoid_weight: self.f32()?,
+        })
+    }
+
+    pub(crate) fn obstacles(&mut self) -> Result<Vec<Obstacle>> {
+        let len = self.u32()? as usize;
+        let mut obstacles = Vec::with_capacity(len.min(self.0.len()));
+        for _ in 0..len {
+            obstacles.push(match self.u8()? {
+                0 => Obstacle::corners(self.vec2()?, self.vec2()?),
+                1 => Obstacle::Circle {
+                    center: self.vec2()?,
+                    radius: self.f32()?,
+                },
+                n => bail!("invalid obstacle {n}"),
+            });
+        }
+
+        Ok(obstacles)
+    }
+
+    /// Reads a spawn pattern, checking its mask is for `species` species.
+    pub(crate) fn spawn_pattern(&mut self, species: usize) -> Result<SpawnPattern> {
+        Ok(match self.u8()? {
+            0 => SpawnPattern::Uniform,
+            1 => SpawnPattern::PoissonDisk {
+                min_distance: self.f32()?,
+            },
+            2 => SpawnPattern::Clusters {
+                spread: self.f32()?,
+            },
+            3 => SpawnPattern::Rings,
+            4 => SpawnPattern::Stripes,
+            5 => {
+                let (width, height) = (self.u32()?, self.u32()?);
+                let len = (width as usize)
+                    .checked_mul(height as usize)
+                    .and_then(|pixels| pixels.checked_mul(4))
+                    .ok_or_else(|| anyhow!("spawn mask too large"))?;
+                let pixels = self.take(len)?.to_vec();
+                let image = image::RgbaImage::from_raw(width, height, pixels).unwrap();
+
+                let count = self.u32()? as usize;
+                let mut colors = Vec::with_capacity(count.min(self.0.len()));
+                for _ in 0..count {
+                    colors.push((self.array()?, self.species(species)?));
+                }
+
+                SpawnPattern::Mask(SpawnMask::new(image, colors))
+            }
+            n => bail!("invalid spawn pattern {n}"),
+        })
+    }
+
+    /// Reads a fight model, checking its odds are for `species` species.
+    pub(crate) fn fight(&mut self, species: usize) -> Result<FightModel> {
+        let odds = self.option(|r| {
+            let timing = match r.u8()? {
+                0 => FightTiming::PerContact,
+                1 => FightTiming::PerSecond,
+                n => bail!("invalid fight timing {n}"),
+            };
+            let len = r.u32()? as usize;
+            ensure!(len == species, "the win matrix does not match the ruleset");
+
+            let mut odds = WinMatrix::new(len, timing);
+            for a in 0..len {
+                for b in 0..len {
+                    let p = r.f32()?;
+                    if p > 0.0 {
+                        odds.set(Species(a as u8), Species(b as u8), p)?;
+                    }
+                }
+            }
+            Ok(odds)
+        })?;
+        let ties = match self.u8()? {
+            0 => TieBreak::Nothing,
+            1 => TieBreak::PushApart,
+            2 => TieBreak::RandomConversion,
+            n => bail!("invalid tie break {n}"),
+        };
+
+        let combat = self.option(|r| {
+            Ok(Combat {
+                damage_won: r.f32()?,
+                damage_tied: r.f32()?,
+                damage_lost: r.f32()?,
+                cooldown: r.f32()?,
+                healing: r.f32()?,
+            })
+        })?;
+
+        Ok(FightModel { odds, ties, combat })
+    }
+
+    /// Reads mutations, checking their matrix is for `species` species.
+    pub(crate) fn mutation(&mut self, species: usize) -> Result<Mutation> {
+        let rate = self.f32()?;
+        let matrix = self.option(|r| {
+            let len = r.u32()? as usize;
+            ensure!(
+                len == species,
+                "the mutation matrix does not match the ruleset"
+            );
+
+            let mut matrix = MutationMatrix::new(len);
+            for a in 0..len {
+                for b in 0..len {
+                    let weight = r.f32()?;
+                    if a != b {
+                        matrix.set(Species(a as u8), Species(b as u8), weight)?;
+                    }
+                }
+            }
+            Ok(matrix)
+        })?;
+
+        Ok(Mutation { rate, matrix })
+    }
+
+    pub(crate) fn ecology(&mut self) -> Result<Option<Ecology>> {
+        self.option(|r| {
+            Ok(Ecology {
+                initial_energy: r.f32()?,
+                prey_energy: r.f32()?,
+                metabolism: r.f32()?,
+                reproduction_threshold: r.f32()?,
+                carrying_capacity: r.u64()? as usize,
+            })
+        })
+    }
+
+    pub(crate) fn lattice(&mut self) -> Result<Option<LatticeScenario>> {
+        self.option(|r| {
+            Ok(LatticeScenario {
+                width: r.u32()? as usize,
+                height: r.u32()? as usize,
+                rates: LatticeRates {
+                    selection: r.f32()?,
+                    reproduction: r.f32()?,
+                    exchange: r.f32()?,
+                },
+                schedule: match r.u8()? {
+                    0 => LatticeSchedule::Asynchronous,
+                    1 => LatticeSchedule::Synchronous,
+                    n => bail!("invalid lattice schedule {n}"),
+                },
+            })
+        })
+    }
+
+    pub(crate) fn pairs(&mut self) -> Result<Vec<(usize, usize)>> {
+        let len = self.u32()? as usize;
+        let mut pairs = Vec::with_capacity(len.min(self.0.len()));
+        for _ in 0..len {
+            pairs.push((self.u32()? as usize, self.u32()? as usize));
+        }
+
+        Ok(pairs)
+    }
+
+    pub(crate) fn boundary(&mut self) -> Result<Boundary> {
+        match self.u8()? {
+            0 => Ok(Boundary::Wrap),
+            1 => Ok(Boundary::Reflect),
+            2 => Ok(Boundary::Absorb),
+            n => bail!("invalid boundary {n}"),
+        }
+    }
+
+    pub(crate) fn stalemate(&mut self) -> Result<Stalemate> {
+        Ok(Stalemate {
+            time_limit: self.option(Self::f64)?,
+            quiet_period: self.option(Self::f64)?,
+        })
+    }
+
+    pub(crate) fn ruleset(&mut self) -> Result<Ruleset> {
+        let len = self.u32()? as usize;
+        ensure!(len <= Ruleset::MAX_SPECIES, "too many species");
+
+        let infos = (0..len)
+            .map(|_| {
+                let info = SpeciesInfo::new(self.string()?, [self.u32()?, self.u32()?]);
+                Ok(info.tint(self.array()?))
+            })
+            .collect::<Result<_>>()?;
+        let mut ruleset = Ruleset::new(infos)?;
+        for a in 0..len {
+            for b in 0..len {
+                match self.u8()? {
+                    0 => ruleset.set_beats(Species(a as u8), Species(b as u8))?,
+                    1 | 2 => {}
+                    n => bail!("invalid fight result {n}"),
+                }
+            }
+        }
+
+        Ok(ruleset)
+    }
+
+    /// Reads one of the first `species` species.
+    pub(crate) fn species(&mut self, species: usize) -> Result<Species> {
+        let kind = Species(self.u8()?);
+        ensure!(kind.index() < species, "unknown species {}", kind.0);
+        Ok(kind)
+    }
+
+    /// Reads agents, checking they belong to one of the `species` species.
+    pub(crate) fn agents(&mut self, species: usize) -> Result<Vec<Agent>> {
+        let len = self.u32()? as usize;
+        let mut agents = Vec::with_capacity(len.min(self.0.len()));
+        for _ in 0..len {
+            agents.push(self.agent(species)?);
+        }
+
+        Ok(agents)
+    }
+
+    pub(crate) fn agent(&mut self, species: usize) -> Result<Agent> {
+        let (position, velocity, kind) = (self.vec2()?, self.vec2()?, self.species(species)?);
+
+        Ok(Agent {
+            health: self.f32()?,
+            cooldown: self.f32()?,
+            energy: self.f32()?,
+            ..Agent::new(position, velocity, kind)
+        })
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{Finish, Species, Vec2, World};
+
+/// Something that happened in a [`World`], queued while it
+/// records events.
+///
+/// `tick` is the value of [`World::tick`] right after
+/// the event, agent indices are into [`World::agents`]
+/// as it is at the end of that step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimulationEvent {
+    /// `attacker` won a fight and turned `victim`, formerly of species
+    /// `from`, into one of its own `species`.
+    Conversion {
+        tick: u64,
+        attacker: usize,
+        victim: usize,
+        species: Species,
+        from: Species,
+        position: Vec2,
+    },
+    /// An agent mutated from one species into another.
+    Mutation {
+        tick: u64,
+        agent: usize,
+        from: Species,
+        to: Species,
+        position: Vec2,
+    },
+    /// An agent was born or added with [`World::spawn`].
+    Spawn {
+        tick: u64,
+        agent: usize,
+        species: Species,
+        position: Vec2,
+    },
+    /// An agent left the world, absorbed agents right away and the others
+    /// at the end of the step. The agents after it move down one index.
+    Death {
+        tick: u64,
+        species: Species,
+        position: Vec2,
+        cause: DeathCause,
+    },
+    /// The last agent of `species` disappeared.
+    Extinction { tick: u64, species: Species },
+    /// The world finished, see [`World::finish`].
+    Finish { tick: u64, finish: Finish },
+}
+
+impl SimulationEvent {
+    pub fn tick(&self) -> u64 {
+        match *self {
+            Self::Conversion { tick, .. }
+            | Self::Mutation { tick, .. }
+            | Self::Spawn { tick, .. }
+            | Self::Death { tick, .. }
+            | Self::Extinction { tick, .. }
+            | Self::Finish { tick, .. } => tick,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathCause {
+    /// Eaten by an agent of the `predator` species in an
+    /// [`Ecology`](crate::Ecology).
+    Eaten { predator: Species },
+    /// Ran out of energy in an [`Ecology`](crate::Ecology).
+    Starved,
+    /// Crossed an absorbing edge of the arena.
+    Absorbed,
+}
+
+/// Receives the events of a world, see [`EventBus`].
+pub trait Observer {
+    fn notify(&mut self, event: &SimulationEvent);
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(&SimulationEvent),
+{
+    fn notify(&mut self, event: &SimulationEvent) {
+        self(event)
+    }
+}
+
+/// Hands the events queued by a world to every registered observer, in the
+/// order they registered.
+#[derive(Default)]
+pub struct EventBus {
+    observers: Vec<Box<dyn Observer>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe<O>(&mut self, observer: O)
+    where
+        O: Observer + 'static,
+    {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn len(&self) -> usize {
+        self.observers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    pub fn publish(&mut self, event: &SimulationEvent) {
+        for observer in &mut self.observers {
+            observer.notify(event);
+        }
+    }
+
+    /// Drains the events queued by `world`, meant to be called after every
+    /// step. Returns the number of events published.
+    pub fn dispatch(&mut self, world: &mut World) -> usize {
+        let mut count = 0;
+        for event in world.drain_events() {
+            self.publish(&event);
+            count += 1;
+        }
+        count
+    }
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::{ensure, Result};
+
+use crate::{FightResults, Ruleset, Species};
+
+/// How the contacts between agents are resolved.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FightModel {
+    /// Chances of conversion, `None` lets the ruleset decide and the winner
+    /// always converts the loser.
+    pub odds: Option<WinMatrix>,
+    /// What happens to agents in contact when neither can convert the other.
+    pub ties: TieBreak,
+    /// Hit points, agents then only convert once their health runs out and
+    /// the odds are ignored.
+    pub combat: Option<Combat>,
+}
+
+impl FightModel {
+    pub fn deterministic() -> Self {
+        Self::default()
+    }
+
+    pub fn probabilistic(odds: WinMatrix) -> Self {
+        Self {
+            odds: Some(odds),
+            ..Self::default()
+        }
+    }
+
+    pub fn ties(mut self, ties: TieBreak) -> Self {
+        self.ties = ties;
+        self
+    }
+
+    pub fn combat(mut self, combat: Combat) -> Self {
+        self.combat = Some(combat);
+        self
+    }
+
+    /// Whether `a` and `b` can not convert one another.
+    pub fn is_tie(&self, ruleset: &Ruleset, a: Species, b: Species) -> bool {
+        match (&self.combat, &self.odds) {
+            (Some(combat), _) => {
+                a == b
+                    || combat.damage(ruleset.fight(a, b)) == 0.0
+                        && combat.damage(ruleset.fight(b, a)) == 0.0
+            }
+            (None, Some(odds)) => odds.get(a, b) == 0.0 && odds.get(b, a) == 0.0,
+            (None, None) => ruleset.fight(a, b) == FightResults::Tied,
+        }
+    }
+}
+
+/// Resolution of contacts neither agent can win.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    /// The agents carry on.
+    #[default]
+    Nothing,
+    /// The agents are moved apart until they no longer touch.
+    PushApart,
+    /// A coin flip picks which agent converts the other.
+    RandomConversion,
+}
+
+impl TieBreak {
+    pub fn name(self) -> &'static str {
+        match self {
+            TieBreak::Nothing => "nothing",
+            TieBreak::PushApart => "push_apart",
+            TieBreak::RandomConversion => "random",
+        }
+    }
+}
+
+impl std::str::FromStr for TieBreak {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nothing" => Ok(TieBreak::Nothing),
+            "push_apart" => Ok(TieBreak::PushApart),
+            "random" => Ok(TieBreak::RandomConversion),
+            _ => Err(anyhow::anyhow!("unknown tie break {s:?}")),
+        }
+    }
+}
+
+/// Hit points combat, agents in contact hit each other whenever their
+/// cooldown allows and convert to the species of the agent that takes their
+/// health to zero. Health goes from 1 when unhurt down to 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Combat {
+    /// Health a hit takes from a species the attacker beats.
+    pub damage_won: f32,
+    /// Health a hit takes from a species the attacker ties with.
+    pub damage_tied: f32,
+    /// Health a hit takes from a species that beats the attacker.
+    pub damage_lost: f32,
+    /// Seconds between two hits of the same agent.
+    pub cooldown: f32,
+    /// Health regained per second.
+    pub healing: f32,
+}
+
+impl Combat {
+    /// Health taken by a hit from an attacker with `result` against its victim.
+    pub fn damage(&self, result: FightResults) -> f32 {
+        match result {
+            FightResults::Won => self.damage_won,
+            FightResults::Tied => self.damage_tied,
+            FightResults::Lost => self.damage_lost,
+        }
+    }
+}
+
+impl Default for Combat {
+    fn default() -> Self {
+        Self {
+            damage_won: 0.34,
+            damage_tied: 0.0,
+            damage_lost: 0.1,
+            cooldown: 0.5,
+            healing: 0.05,
+        }
+    }
+}
+
+/// When the chances of a [`WinMatrix`] are rolled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FightTiming {
+    /// Once, on the step two agents start touching.
+    #[default]
+    PerContact,
+    /// Every step, the chances being per second of sustained contact.
+    PerSecond,
+}
+
+/// Probability that an agent of one species converts an agent of another
+/// one they touch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WinMatrix {
+    len: usize,
+    /// `probabilities[a * len + b]` is the chance of `a` converting `b`.
+    probabilities: Vec<f32>,
+    pub timing: FightTiming,
+}
+
+impl WinMatrix {
+    /// Matrix where nobody converts anybody, for `len` species.
+    pub fn new(len: usize, timing: FightTiming) -> Self {
+        Self {
+            len,
+            probabilities: vec![0.0; len * len],
+            timing,
+        }
+    }
+
+    /// Matrix where every winner of the ruleset converts its losers with
+    /// probability `p`.
+    pub fn from_ruleset(ruleset: &Ruleset, p: f32, timing: FightTiming) -> Self {
+        let mut matrix = Self::new(ruleset.len(), timing);
+        for a in ruleset.species() {
+            for b in ruleset.species() {
+                if ruleset.fight(a, b) == FightResults::Won {
+                    matrix.probabilities[a.index() * matrix.len + b.index()] = p;
+                }
+            }
+        }
+
+        matrix
+    }
+
+    /// Sets the chance of `a` converting `b`, fails unless it is a
+    /// probability leaving room for the chance of `b` converting `a`.
+    pub fn set(&mut self, a: Species, b: Species, p: f32) -> Result<()> {
+        let (i, j) = (a.index(), b.index());
+        ensure!(i < self.len && j < self.len, "unknown species in {i} > {j}");
+        ensure!(
+            (0.0..=1.0).contains(&p),
+            "probabilities must be between 0 and 1, not {p}"
+        );
+        ensure!(
+            i == j || p + self.probabilities[j * self.len + i] <= 1.0,
+            "the chances of {i} and {j} converting one another add up to more than 1"
+        );
+
+        self.probabilities[i * self.len + j] = p;
+        Ok(())
+    }
+
+    pub fn get(&self, a: Species, b: Species) -> f32 {
+        self.probabilities[a.index() * self.len + b.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Chances of `a` converting `b` and of `b` converting `a` during a step
+    /// of `dt` seconds.
+    pub fn chances(&self, a: Species, b: Species, dt: f32) -> (f32, f32) {
+        let (p, q) = (self.get(a, b), self.get(b, a));
+        match self.timing {
+            FightTiming::PerContact => (p, q),
+            FightTiming::PerSecond => {
+                (1.0 - libm::powf(1.0 - p, dt), 1.0 - libm::powf(1.0 - q, dt))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrices_follow_the_ruleset() {
+        let ruleset = Ruleset::classic();
+        let (rock, paper, scissors) = (Species(0), Species(1), Species(2));
+        let mut odds = WinMatrix::from_ruleset(&ruleset, 0.75, FightTiming::PerSecond);
+
+        assert_eq!(odds.get(paper, rock), 0.75);
+        assert_eq!(odds.get(rock, paper), 0.0);
+        assert!(odds.set(rock, paper, 0.5).is_err());
+        odds.set(rock, paper, 0.25).unwrap();
+
+        let (p, q) = odds.chances(paper, rock, 0.5);
+        assert!((p - 0.5).abs() < 1e-6);
+        assert!(q < 0.25);
+
+        let model = FightModel::probabilistic(odds);
+        assert!(model.is_tie(&ruleset, rock, rock));
+        assert!(!model.is_tie(&ruleset, rock, scissors));
+    }
+}
@@ -0,0 +1,533 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::{FightResults, Finish, Rng, Ruleset, SimulationState, Species};
+
+/// Order in which the cells of a [`Lattice`] are updated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LatticeSchedule {
+    /// One random cell and neighbour at a time, as many times per
+    /// generation as there are cells.
+    #[default]
+    Asynchronous,
+    /// Every cell at once from the state of the previous generation,
+    /// exchanges then swap the pairs of a random domino tiling.
+    Synchronous,
+}
+
+impl LatticeSchedule {
+    pub fn name(self) -> &'static str {
+        match self {
+            LatticeSchedule::Asynchronous => "async",
+            LatticeSchedule::Synchronous => "sync",
+        }
+    }
+}
+
+impl std::str::FromStr for LatticeSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "async" => Ok(LatticeSchedule::Asynchronous),
+            "sync" => Ok(LatticeSchedule::Synchronous),
+            _ => Err(anyhow::anyhow!("unknown lattice schedule {s:?}")),
+        }
+    }
+}
+
+/// Relative rates of the May-Leonard moves between neighbouring cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatticeRates {
+    /// A shape empties the cell of a neighbour it beats.
+    pub selection: f32,
+    /// A shape copies itself into an empty neighbour.
+    pub reproduction: f32,
+    /// Two neighbours swap places, empty cells included.
+    pub exchange: f32,
+}
+
+impl Default for LatticeRates {
+    fn default() -> Self {
+        Self {
+            selection: 1.0,
+            reproduction: 1.0,
+            exchange: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Move {
+    Selection,
+    Reproduction,
+    Exchange,
+}
+
+impl LatticeRates {
+    fn pick(&self, rng: &mut Rng) -> Option<Move> {
+        let total = self.selection + self.reproduction + self.exchange;
+        if total <= 0.0 {
+            return None;
+        }
+
+        let roll = rng.next_f32() * total;
+        Some(if roll < self.selection {
+            Move::Selection
+        } else if roll < self.selection + self.reproduction {
+            Move::Reproduction
+        } else {
+            Move::Exchange
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LatticeBuilder {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    pub rates: LatticeRates,
+    pub schedule: LatticeSchedule,
+    pub ruleset: Ruleset,
+    /// Cells initially occupied by every species, the others start empty.
+    pub population: Vec<(Species, usize)>,
+}
+
+impl LatticeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn size(mut self, width: usize, height: usize) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn rates(mut self, rates: LatticeRates) -> Self {
+        self.rates = rates;
+        self
+    }
+
+    pub fn schedule(mut self, schedule: LatticeSchedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    pub fn ruleset(mut self, ruleset: Ruleset) -> Self {
+        self.ruleset = ruleset;
+        self
+    }
+
+    pub fn population<S>(mut self, species: S, count: usize) -> Self
+    where
+        S: Into<Species>,
+    {
+        self.population.push((species.into(), count));
+        self
+    }
+
+    /// Creates the lattice and scatters the population over random cells,
+    /// species that no longer fit are left out.
+    ///
+    /// Fails if the lattice has no cells or a species of the population is
+    /// not in the ruleset.
+    pub fn build(self) -> Result<Lattice> {
+        ensure!(
+            self.width > 0 && self.height > 0,
+            "the lattice must have at least one cell, not {} x {}",
+            self.width,
+            self.height
+        );
+        let len = self.ruleset.len();
+        if let Some(&(species, _)) = self.population.iter().find(|(s, _)| s.index() >= len) {
+            bail!("unknown species {} in a ruleset of {len}", species.0);
+        }
+
+        let mut lattice = Lattice::new(self.width, self.height, self.seed);
+        lattice.set_ruleset(self.ruleset);
+        lattice.set_rates(self.rates);
+        lattice.set_schedule(self.schedule);
+
+        let mut free = (0..lattice.cells.len()).collect::<Vec<_>>();
+        lattice.rng.shuffle(&mut free);
+        let mut free = free.into_iter();
+        for (species, count) in self.population {
+            for index in free.by_ref().take(count) {
+                lattice.cells[index] = Some(species);
+            }
+        }
+
+        Ok(lattice)
+    }
+}
+
+impl Default for LatticeBuilder {
+    fn default() -> Self {
+        Self {
+            width: 200,
+            height: 150,
+            seed: 0,
+            rates: LatticeRates::default(),
+            schedule: LatticeSchedule::default(),
+            ruleset: Ruleset::default(),
+            population: Vec::new(),
+        }
+    }
+}
+
+/// Spatial rock paper scissors on a grid wrapping around like a torus,
+/// every cell holds one shape or is empty.
+#[derive(Debug, Clone)]
+pub struct Lattice {
+    width: usize,
+    height: usize,
+    /// Row by row from the top left corner.
+    cells: Vec<Option<Species>>,
+    ruleset: Ruleset,
+    rates: LatticeRates,
+    schedule: LatticeSchedule,
+    rng: Rng,
+    state: SimulationState,
+    generation: u64,
+    finish: Option<Finish>,
+}
+
+impl Lattice {
+    /// Creates an empty lattice, every random decision it makes is drawn
+    /// from a generator seeded with `seed`.
+    pub fn new(width: usize, height: usize, seed: u64) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![None; width * height],
+            ruleset: Ruleset::default(),
+            rates: LatticeRates::default(),
+            schedule: LatticeSchedule::default(),
+            rng: Rng::new(seed),
+            state: SimulationState::Play,
+            generation: 0,
+            finish: None,
+        }
+    }
+
+    pub fn set_ruleset(&mut self, ruleset: Ruleset) {
+        self.ruleset = ruleset;
+    }
+
+    pub fn ruleset(&self) -> &Ruleset {
+        &self.ruleset
+    }
+
+    pub fn set_rates(&mut self, rates: LatticeRates) {
+        self.rates = rates;
+    }
+
+    pub fn rates(&self) -> &LatticeRates {
+        &self.rates
+    }
+
+    pub fn set_schedule(&mut self, schedule: LatticeSchedule) {
+        self.schedule = schedule;
+    }
+
+    pub fn schedule(&self) -> LatticeSchedule {
+        self.schedule
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Row by row from the top left corner.
+    pub fn cells(&self) -> &[Option<Species>] {
+        &self.cells
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Species> {
+        self.cells[y * self.width + x]
+    }
+
+    /// Panics if the species is not in the ruleset.
+    pub fn set(&mut self, x: usize, y: usize, cell: Option<Species>) {
+        if let Some(species) = cell {
+            assert!(
+                species.index() < self.ruleset.len(),
+                "unknown species {}",
+                species.0
+            );
+        }
+        self.cells[y * self.width + x] = cell;
+    }
+
+    /// Number of cells held by every species, indexed by species.
+    pub fn populations(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.ruleset.len()];
+        for species in self.cells.iter().flatten() {
+            counts[species.index()] += 1;
+        }
+        counts
+    }
+
+    pub fn state(&self) -> SimulationState {
+        self.state
+    }
+
+    /// Number of generations simulated so far.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// How the simulation ended, once it is in [`SimulationState::Finish`].
+    pub fn finish(&self) -> Option<Finish> {
+        self.finish
+    }
+
+    /// Simulates one generation, in which every cell is updated once on
+    /// average. The lattice finishes once at most one species is left, the
+    /// elapsed time of the finish counting generations.
+    ///
+    /// Does nothing unless the lattice is in [`SimulationState::Play`].
+    pub fn step(&mut self) -> Option<Finish> {
+        if self.state != SimulationState::Play {
+            return None;
+        }
+
+        self.advance()
+    }
+
+    pub fn pause(&mut self) {
+        if self.state == SimulationState::Play {
+            self.state = SimulationState::Pause;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.state == SimulationState::Pause {
+            self.state = SimulationState::Play;
+        }
+    }
+
+    /// Advances a paused lattice by a single generation, leaving it paused.
+    pub fn step_once(&mut self) -> Option<Finish> {
+        if self.state != SimulationState::Pause {
+            return None;
+        }
+
+        let finish = self.advance();
+        if finish.is_none() {
+            self.state = SimulationState::Pause;
+        }
+        finish
+    }
+
+    fn advance(&mut self) -> Option<Finish> {
+        match self.schedule {
+            LatticeSchedule::Asynchronous => {
+                for _ in 0..self.cells.len() {
+                    self.interact();
+                }
+            }
+            LatticeSchedule::Synchronous => self.update_all(),
+        }
+        self.generation += 1;
+        self.state = SimulationState::Play;
+
+        let mut remaining = self.populations().into_iter().enumerate();
+        let mut alive = remaining.by_ref().filter(|&(_, count)| count > 0);
+        let winner = alive.next().map(|(index, _)| Species(index as u8));
+        if alive.next().is_some() {
+            return None;
+        }
+
+        let finish = Finish {
+            winner,
+            elapsed: self.generation as f64,
+        };
+        self.state = SimulationState::Finish;
+        self.finish = Some(finish);
+        Some(finish)
+    }
+
+    /// Index of the neighbour of `index` in one of the four `direction`s.
+    fn neighbour(&self, index: usize, direction: usize) -> usize {
+        let (x, y) = (index % self.width, index / self.width);
+        let (x, y) = match direction {
+            0 => ((x + 1) % self.width, y),
+            1 => ((x + self.width - 1) % self.width, y),
+            2 => (x, (y + 1) % self.height),
+            _ => (x, (y + self.height - 1) % self.height),
+        };
+        y * self.width + x
+    }
+
+    /// Lets a random cell and one of its neighbours interact.
+    fn interact(&mut self) {
+        let a = self.rng.below(self.cells.len());
+        let direction = self.rng.below(4);
+        let b = self.neighbour(a, direction);
+
+        match (self.rates.pick(&mut self.rng), self.cells[a], self.cells[b]) {
+            (Some(Move::Selection), Some(x), Some(y)) => match self.ruleset.fight(x, y) {
+                FightResults::Won => self.cells[b] = None,
+                FightResults::Lost => self.cells[a] = None,
+                FightResults::Tied => {}
+            },
+            (Some(Move::Reproduction), Some(_), None) => self.cells[b] = self.cells[a],
+            (Some(Move::Reproduction), None, Some(_)) => self.cells[a] = self.cells[b],
+            (Some(Move::Exchange), _, _) => self.cells.swap(a, b),
+            _ => {}
+        }
+    }
+
+    /// Lets every cell look at a random neighbour of the previous
+    /// generation, then swaps neighbours.
+    fn update_all(&mut self) {
+        let previous = self.cells.clone();
+        let mut exchanges = 0;
+        for a in 0..previous.len() {
+            let direction = self.rng.below(4);
+            let b = self.neighbour(a, direction);
+
+            // a cell only ever changes itself, so no two updates conflict
+            match (self.rates.pick(&mut self.rng), previous[a], previous[b]) {
+                (Some(Move::Selection), Some(x), Some(y))
+                    if self.ruleset.fight(y, x) == FightResults::Won =>
+                {
+                    self.cells[a] = None;
+                }
+                (Some(Move::Reproduction), None, Some(_)) => self.cells[a] = previous[b],
+                (Some(Move::Exchange), _, _) => exchanges += 1,
+                _ => {}
+            }
+        }
+
+        // swap as many disjoint pairs as cells picked an exchange
+        let horizontal = self.rng.chance(0.5);
+        let offset = self.rng.below(2);
+        let (along, across) = if horizontal {
+            (self.width, self.height)
+        } else {
+            (self.height, self.width)
+        };
+        let pairs = along / 2 * across;
+        let p = (exchanges as f32 / pairs.max(1) as f32).min(1.0);
+
+        for line in 0..across {
+            for k in 0..along / 2 {
+                if !self.rng.chance(p) {
+                    continue;
+                }
+
+                let (first, second) = ((offset + 2 * k) % along, (offset + 2 * k + 1) % along);
+                let (a, b) = if horizontal {
+                    (line * self.width + first, line * self.width + second)
+                } else {
+                    (first * self.width + line, second * self.width + line)
+                };
+                self.cells.swap(a, b);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Shape;
+
+    fn lattice(rates: LatticeRates, schedule: LatticeSchedule) -> Lattice {
+        LatticeBuilder::new()
+            .size(20, 15)
+            .seed(3)
+            .rates(rates)
+            .schedule(schedule)
+            .population(Shape::Rock, 60)
+            .population(Shape::Paper, 60)
+            .population(Shape::Scissors, 60)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn builder_scatters_the_population() {
+        let lattice = lattice(LatticeRates::default(), LatticeSchedule::Asynchronous);
+
+        assert_eq!(lattice.populations(), [60, 60, 60]);
+        assert_eq!(lattice.cells().iter().filter(|c| c.is_none()).count(), 120);
+
+        assert!(LatticeBuilder::new().size(0, 15).build().is_err());
+    }
+
+    #[test]
+    fn moves_follow_the_rates() {
+        for schedule in [LatticeSchedule::Asynchronous, LatticeSchedule::Synchronous] {
+            let exchange = LatticeRates {
+                selection: 0.0,
+                reproduction: 0.0,
+                exchange: 1.0,
+            };
+            let mut lattice = lattice(exchange, schedule);
+            let before = lattice.cells().to_vec();
+            for _ in 0..10 {
+                lattice.step();
+            }
+            assert_eq!(lattice.populations(), [60, 60, 60]);
+            assert_ne!(lattice.cells(), before);
+
+            let reproduction = LatticeRates {
+                selection: 0.0,
+                exchange: 0.0,
+                ..exchange
+            };
+            let mut lattice = lattice.clone();
+            lattice.set_rates(LatticeRates {
+                reproduction: 1.0,
+                ..reproduction
+            });
+            for _ in 0..100 {
+                lattice.step();
+            }
+            assert!(lattice.cells().iter().all(Option::is_some));
+
+            // selection alone only ever empties cells
+            let mut lattice = lattice.clone();
+            lattice.set_rates(LatticeRates {
+                selection: 1.0,
+                ..reproduction
+            });
+            let before = lattice.populations();
+            lattice.step();
+            let after = lattice.populations();
+            assert!(after.iter().zip(&before).all(|(a, b)| a <= b));
+            assert!(after.iter().sum::<usize>() < 300);
+        }
+    }
+
+    #[test]
+    fn finishes_when_one_species_is_left() {
+        let mut lattice = LatticeBuilder::new()
+            .size(10, 10)
+            .population(Shape::Rock, 50)
+            .population(Shape::Scissors, 50)
+            .build()
+            .unwrap();
+
+        let mut finish = None;
+        while finish.is_none() {
+            finish = lattice.step();
+        }
+
+        assert_eq!(finish.unwrap().winner, Some(Shape::Rock.into()));
+        assert_eq!(lattice.state(), SimulationState::Finish);
+    }
+}
@@ -1,6 +1,32 @@
+mod arena;
+mod batch;
+mod behavior;
 mod context;
+mod ecology;
+mod encoding;
+mod event;
+mod fight;
+mod lattice;
 mod math;
+mod mean_field;
+mod mutation;
+mod obstacle;
 mod renderer;
+mod replay;
+mod rng;
+mod ruleset;
+mod scenario;
 mod simulation;
+mod spatial;
+mod spawn;
+mod statistics;
+mod steering;
+mod sweep;
+mod world;
 
-pub use crate::{context::*, math::*, renderer::*, simulation::*};
+pub use crate::{
+    arena::*, batch::*, behavior::*, context::*, ecology::*, event::*, fight::*, lattice::*,
+    math::*, mean_field::*, mutation::*, obstacle::*, renderer::*, replay::*, rng::*, ruleset::*,
+    scenario::*, simulation::*, spatial::*, spawn::*, statistics::*, steering::*, sweep::*,
+    world::*,
+};
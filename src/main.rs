@@ -1,50 +1,603 @@
 use rps_simulator::*;
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+const SPRITE_SIZE: f32 = 24.0;
+const STATISTICS_INTERVAL: u64 = 60;
+const STATISTICS_FILE: &str = "statistics.csv";
+const SNAPSHOT_FILE: &str = "snapshot.rpsw";
+const REPLAY_FILE: &str = "replay.rpsr";
+const OBSTACLE_COLOR: [u8; 4] = [96, 96, 96, 255];
+
+/// Where the inputs applied to the world come from.
+enum Driver {
+    /// The user, every input is recorded.
+    Record(Recorder),
+    /// A replay, the user can only change the speed.
+    Play(ReplayPlayer),
+    /// The user, once a snapshot is loaded the run can no longer be recorded.
+    Free,
+}
 
 struct Simulation {
+    world: World,
+    driver: Driver,
+    /// Species spawned by clicking.
+    selected: Species,
+    cursor: Vec2,
+    /// Agent positions before the last update, drawing blends them with the
+    /// current ones.
+    previous: Vec<Vec2>,
     mesh: Mesh,
     texture: Texture,
     instance_buffer: InstanceBuffer,
+    obstacles: ObstacleSprites,
+}
+
+/// Every rectangle and every circle of the world drawn in one call each,
+/// scaling a unit mesh.
+struct ObstacleSprites {
+    rect_mesh: Mesh,
+    circle_mesh: Mesh,
+    texture: Texture,
+    rects: Option<InstanceBuffer>,
+    circles: Option<InstanceBuffer>,
+}
+
+impl ObstacleSprites {
+    fn new(ctx: &Context, world: &World) -> Self {
+        let color = image::RgbaImage::from_pixel(1, 1, image::Rgba(OBSTACLE_COLOR));
+        let texture = ctx
+            .create_texture(&image::DynamicImage::ImageRgba8(color), 1, 1)
+            .unwrap();
+
+        let mut sprites = Self {
+            rect_mesh: ctx.create_mesh(1.0, 1.0),
+            circle_mesh: ctx.create_circle_mesh(1.0, 48),
+            texture,
+            rects: None,
+            circles: None,
+        };
+        sprites.rebuild(ctx, world);
+        sprites
+    }
+
+    fn rebuild(&mut self, ctx: &Context, world: &World) {
+        let mut rects = Vec::new();
+        let mut circles = Vec::new();
+        for obstacle in world.obstacles() {
+            match *obstacle {
+                Obstacle::Rect { min, max } => rects.push(Instance::new(
+                    Transform::identity()
+                        .scale([max.x - min.x, max.y - min.y])
+                        .translate([min.x, min.y]),
+                    [0, 0],
+                )),
+                Obstacle::Circle { center, radius } => circles.push(Instance::new(
+                    Transform::identity()
+                        .scale([radius, radius])
+                        .translate([center.x - radius, center.y - radius]),
+                    [0, 0],
+                )),
+            }
+        }
+
+        let buffer = |instances: Vec<Instance>| {
+            (!instances.is_empty()).then(|| ctx.create_instance_buffer(&instances))
+        };
+        self.rects = buffer(rects);
+        self.circles = buffer(circles);
+    }
+
+    fn commands(&self) -> Vec<DrawMeshCommand<'_>> {
+        [
+            (&self.rect_mesh, &self.rects),
+            (&self.circle_mesh, &self.circles),
+        ]
+        .into_iter()
+        .filter_map(|(mesh, instances)| {
+            Some(DrawMeshCommand {
+                texture_attachment: Some(&self.texture),
+                instance_buffer: Some(instances.as_ref()?),
+                clear_color: None,
+                mesh,
+            })
+        })
+        .collect()
+    }
 }
 
 impl Simulation {
-    pub fn new(ctx: &mut Context) -> Self {
-        let mesh = ctx.create_mesh(60.0, 60.0);
+    /// Runs `world`, built from `scenario`.
+    pub fn new(
+        ctx: &mut Context,
+        world: World,
+        scenario: &Scenario,
+        replay: Option<Replay>,
+    ) -> Self {
+        let mesh = ctx.create_mesh(SPRITE_SIZE, SPRITE_SIZE);
         let texture = ctx
             .load_texture_atlas("assets/rps_atlas.png", 3, 1)
             .unwrap();
 
-        let instances = [(10.0, 0, 0), (80.0, 1, 0), (150.0, 2, 0)]
-            .into_iter()
-            .map(|(x, uv_x, uv_y)| {
-                Instance::new(Transform::identity().translate([x, 10.0]), [uv_x, uv_y])
-            })
-            .collect::<Vec<_>>();
+        let (width, height) = world.size();
+        ctx.set_view(width, height);
+
+        let previous = positions(&world);
+        let instances = build_instances(&world, &previous, 0.0);
         let instance_buffer = ctx.create_instance_buffer(&instances);
+        let obstacles = ObstacleSprites::new(ctx, &world);
+
+        let driver = match replay {
+            Some(replay) => Driver::Play(ReplayPlayer::new(replay)),
+            None => Driver::Record(Recorder::new(scenario.clone(), ctx.time_step())),
+        };
 
         Self {
+            world,
+            driver,
+            selected: Species(0),
+            cursor: Vec2::ZERO,
+            previous,
             mesh,
             texture,
             instance_buffer,
+            obstacles,
+        }
+    }
+}
+
+impl Simulation {
+    fn input(&mut self, ctx: &Context, input: Input) {
+        let (tick, before) = (self.world.tick(), positions(&self.world));
+
+        let finish = match &mut self.driver {
+            Driver::Record(recorder) => recorder.input(&mut self.world, input),
+            Driver::Play(_) => return,
+            Driver::Free => input.apply(&mut self.world, ctx.time_step()),
+        };
+        self.report(finish);
+
+        // a single step is interpolated from where the agents were, unless
+        // the world was not paused and nothing moved
+        if input != Input::StepOnce {
+            self.previous = positions(&self.world);
+        } else if self.world.tick() != tick {
+            self.previous = before;
+        }
+    }
+
+    /// Index of the agent under the cursor, if any.
+    fn hovered_agent(&self) -> Option<usize> {
+        let radius_squared = (SPRITE_SIZE / 2.0) * (SPRITE_SIZE / 2.0);
+
+        self.world
+            .agents()
+            .iter()
+            .map(|agent| (agent.position - self.cursor).length_squared())
+            .enumerate()
+            .filter(|&(_, distance)| distance <= radius_squared)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    /// Space pauses and resumes, the right arrow steps a paused world, R
+    /// restarts the run, plus or minus change the speed, digits select the
+    /// species spawned by clicking, C saves the statistics, F5 / F9 save and
+    /// load a snapshot and F6 saves the replay.
+    fn key(&mut self, ctx: &mut Context, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Space if self.world.state() == SimulationState::Play => {
+                self.input(ctx, Input::Pause)
+            }
+            VirtualKeyCode::Space => self.input(ctx, Input::Resume),
+            VirtualKeyCode::Right => self.input(ctx, Input::StepOnce),
+            VirtualKeyCode::R => self.input(ctx, Input::Reset),
+            VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                ctx.set_speed(ctx.speed() * 2.0);
+                println!("speed: {}x", ctx.speed());
+            }
+            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                ctx.set_speed(ctx.speed() / 2.0);
+                println!("speed: {}x", ctx.speed());
+            }
+            VirtualKeyCode::Key1
+            | VirtualKeyCode::Key2
+            | VirtualKeyCode::Key3
+            | VirtualKeyCode::Key4
+            | VirtualKeyCode::Key5
+            | VirtualKeyCode::Key6
+            | VirtualKeyCode::Key7
+            | VirtualKeyCode::Key8
+            | VirtualKeyCode::Key9 => {
+                let index = key as usize - VirtualKeyCode::Key1 as usize;
+                if index < self.world.ruleset().len() {
+                    self.selected = Species(index as u8);
+                    println!("spawning {}", self.world.ruleset().name(self.selected));
+                }
+            }
+            VirtualKeyCode::C => {
+                if let Some(statistics) = self.world.statistics() {
+                    match statistics.save_csv(STATISTICS_FILE) {
+                        Ok(()) => println!("statistics saved to {STATISTICS_FILE}"),
+                        Err(e) => eprintln!("{e:#}"),
+                    }
+                }
+            }
+            VirtualKeyCode::F5 => match self.world.save_snapshot(SNAPSHOT_FILE) {
+                Ok(()) => println!("snapshot saved to {SNAPSHOT_FILE}"),
+                Err(e) => eprintln!("{e:#}"),
+            },
+            VirtualKeyCode::F6 => {
+                if let Driver::Record(recorder) = &self.driver {
+                    match recorder.replay(&self.world).save(REPLAY_FILE) {
+                        Ok(()) => println!("replay saved to {REPLAY_FILE}"),
+                        Err(e) => eprintln!("{e:#}"),
+                    }
+                }
+            }
+            VirtualKeyCode::F9 => match World::load_snapshot(SNAPSHOT_FILE) {
+                Ok(world) => {
+                    self.world = world;
+                    self.world.record_statistics(STATISTICS_INTERVAL);
+                    self.previous = positions(&self.world);
+                    self.obstacles.rebuild(ctx, &self.world);
+                    if let Driver::Record(_) = self.driver {
+                        println!("recording stopped, a snapshot can not be replayed");
+                    }
+                    self.driver = Driver::Free;
+
+                    let (width, height) = self.world.size();
+                    ctx.set_view(width, height);
+                    println!("snapshot loaded from {SNAPSHOT_FILE}");
+                }
+                Err(e) => eprintln!("{e:#}"),
+            },
+            _ => {}
+        }
+    }
+
+    fn report(&self, finish: Option<Finish>) {
+        let Some(finish) = finish else {
+            return;
+        };
+
+        match finish.winner {
+            Some(species) => println!(
+                "{} wins after {:.1}s",
+                self.world.ruleset().name(species),
+                finish.elapsed
+            ),
+            None => println!("stalemate after {:.1}s", finish.elapsed),
         }
     }
 }
 
 impl MainLoop for Simulation {
-    fn draw(&mut self, _ctx: &mut Context) -> DrawCommand {
-        DrawCommand::DrawMesh(DrawMeshCommand {
+    fn update(&mut self, _ctx: &mut Context, dt: f32) {
+        if self.world.state() == SimulationState::Play {
+            self.previous = positions(&self.world);
+        }
+
+        let finish = match &mut self.driver {
+            Driver::Record(recorder) => recorder.step(&mut self.world),
+            Driver::Play(player) if !player.is_done() => player.step(&mut self.world),
+            Driver::Play(_) => None,
+            Driver::Free => self.world.step(dt),
+        };
+        self.report(finish);
+    }
+
+    /// Left clicks spawn an agent of the selected species, right clicks
+    /// remove the agent under the cursor.
+    fn event(&mut self, ctx: &mut Context, event: &WindowEvent) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => self.cursor = ctx.view_position(*position),
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let speed = self.world.steering(self.selected).max_speed;
+                let agent = Agent::new(self.cursor, [speed, 0.0], self.selected);
+                self.input(ctx, Input::Spawn(agent));
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => {
+                if let Some(index) = self.hovered_agent() {
+                    self.input(ctx, Input::Remove(index));
+                }
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => self.key(ctx, *key),
+            _ => {}
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context, alpha: f32) -> DrawCommand<'_> {
+        let mut commands = self.obstacles.commands();
+        if self.world.agents().is_empty() {
+            return match commands.first_mut() {
+                Some(first) => {
+                    first.clear_color = Some(wgpu::Color::WHITE);
+                    DrawCommand::DrawMeshes(commands)
+                }
+                None => DrawCommand::Clear(wgpu::Color::WHITE),
+            };
+        }
+
+        // a paused world stays where the last step left it
+        let alpha = match self.world.state() {
+            SimulationState::Play => alpha,
+            _ => 1.0,
+        };
+
+        let instances = build_instances(&self.world, &self.previous, alpha);
+        if instances.len() == self.instance_buffer.range().len() {
+            ctx.update_instance_buffer(&self.instance_buffer, &instances);
+        } else {
+            // absorbing boundaries remove agents
+            self.instance_buffer = ctx.create_instance_buffer(&instances);
+        }
+
+        commands.push(DrawMeshCommand {
             texture_attachment: Some(&self.texture),
             instance_buffer: Some(&self.instance_buffer),
             clear_color: None,
             mesh: &self.mesh,
+        });
+        commands[0].clear_color = Some(wgpu::Color::WHITE);
+
+        DrawCommand::DrawMeshes(commands)
+    }
+}
+
+/// Runs a [`Lattice`] instead of a world, drawing every occupied cell as a
+/// sprite of the atlas or as a plain coloured pixel.
+struct LatticeSimulation {
+    scenario: Scenario,
+    lattice: Lattice,
+    /// Cells are drawn with the palette instead of the atlas.
+    pixels: bool,
+    mesh: Mesh,
+    atlas: Texture,
+    palette: Texture,
+    /// `None` while every cell is empty.
+    instance_buffer: Option<InstanceBuffer>,
+}
+
+impl LatticeSimulation {
+    /// Runs `lattice`, built from `scenario`.
+    fn new(ctx: &mut Context, lattice: Lattice, scenario: &Scenario) -> Self {
+        let species = lattice.ruleset().len() as u32;
+        let palette = ctx
+            .create_texture(
+                &image::DynamicImage::ImageRgba8(palette(species)),
+                species,
+                1,
+            )
+            .unwrap();
+        ctx.set_view(lattice.width() as f32, lattice.height() as f32);
+
+        Self {
+            scenario: scenario.clone(),
+            lattice,
+            // sprites are unreadable on large lattices
+            pixels: true,
+            mesh: ctx.create_mesh(1.0, 1.0),
+            atlas: ctx
+                .load_texture_atlas("assets/rps_atlas.png", 3, 1)
+                .unwrap(),
+            palette,
+            instance_buffer: None,
+        }
+    }
+
+    fn report(&self, finish: Option<Finish>) {
+        let Some(finish) = finish else {
+            return;
+        };
+
+        match finish.winner {
+            Some(species) => println!(
+                "{} wins after {} generations",
+                self.lattice.ruleset().name(species),
+                finish.elapsed
+            ),
+            None => println!("every cell is empty after {} generations", finish.elapsed),
+        }
+    }
+}
+
+impl MainLoop for LatticeSimulation {
+    fn update(&mut self, _ctx: &mut Context, _dt: f32) {
+        let finish = self.lattice.step();
+        self.report(finish);
+    }
+
+    /// Space pauses and resumes, the right arrow steps a paused lattice, R
+    /// restarts it, P switches between sprites and pixels and plus or minus
+    /// change the speed.
+    fn event(&mut self, ctx: &mut Context, event: &WindowEvent) {
+        let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+            ..
+        } = event
+        else {
+            return;
+        };
+
+        match key {
+            VirtualKeyCode::Space if self.lattice.state() == SimulationState::Play => {
+                self.lattice.pause()
+            }
+            VirtualKeyCode::Space => self.lattice.resume(),
+            VirtualKeyCode::Right => {
+                let finish = self.lattice.step_once();
+                self.report(finish);
+            }
+            VirtualKeyCode::R => {
+                if let Ok(Some(lattice)) = self.scenario.build_lattice() {
+                    self.lattice = lattice;
+                }
+            }
+            VirtualKeyCode::P => self.pixels = !self.pixels,
+            VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                ctx.set_speed(ctx.speed() * 2.0);
+                println!("speed: {}x", ctx.speed());
+            }
+            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                ctx.set_speed(ctx.speed() / 2.0);
+                println!("speed: {}x", ctx.speed());
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _alpha: f32) -> DrawCommand<'_> {
+        let width = self.lattice.width();
+        let ruleset = self.lattice.ruleset();
+        let instances = self
+            .lattice
+            .cells()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, cell)| {
+                let species = (*cell)?;
+                let corner = [(i % width) as f32, (i / width) as f32];
+                let transform = Transform::identity().translate(corner);
+                Some(if self.pixels {
+                    Instance::new(transform, [species.0 as u32, 0])
+                } else {
+                    let info = ruleset.info(species);
+                    Instance::new(transform, info.atlas_cell).tint(info.tint)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if instances.is_empty() {
+            return DrawCommand::Clear(wgpu::Color::WHITE);
+        }
+        match &self.instance_buffer {
+            Some(buffer) if buffer.range().len() == instances.len() => {
+                ctx.update_instance_buffer(buffer, &instances);
+            }
+            _ => self.instance_buffer = Some(ctx.create_instance_buffer(&instances)),
+        }
+
+        DrawCommand::DrawMesh(DrawMeshCommand {
+            texture_attachment: Some(if self.pixels {
+                &self.palette
+            } else {
+                &self.atlas
+            }),
+            instance_buffer: self.instance_buffer.as_ref(),
+            clear_color: Some(wgpu::Color::WHITE),
+            mesh: &self.mesh,
         })
     }
 }
 
+/// One pixel per species, with hues evenly spread around the color wheel.
+fn palette(species: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(species.max(1), 1, |x, _| {
+        let hue = x as f32 / species.max(1) as f32 * 6.0;
+        let channel = |offset: f32| {
+            let distance = ((hue - offset).rem_euclid(6.0) - 3.0).abs();
+            ((distance - 1.0).clamp(0.0, 1.0) * 255.0) as u8
+        };
+        image::Rgba([channel(0.0), channel(2.0), channel(4.0), 255])
+    })
+}
+
+fn positions(world: &World) -> Vec<Vec2> {
+    world.agents().iter().map(|agent| agent.position).collect()
+}
+
+/// Sprites placed `alpha` of the way from the `previous` positions to the
+/// current ones.
+fn build_instances(world: &World, previous: &[Vec2], alpha: f32) -> Vec<Instance> {
+    let half_size = SPRITE_SIZE / 2.0;
+    let ruleset = world.ruleset();
+    // agents were removed since, the indices no longer match
+    let previous = (previous.len() == world.agents().len()).then_some(previous);
+
+    world
+        .agents()
+        .iter()
+        .enumerate()
+        .map(|(i, agent)| {
+            let position = match previous.map(|previous| previous[i]) {
+                // agents wrapping around the arena jump instead of sliding across
+                Some(from) if (agent.position - from).length() < SPRITE_SIZE => {
+                    from + (agent.position - from) * alpha
+                }
+                _ => agent.position,
+            };
+            let corner = [position.x - half_size, position.y - half_size];
+            let info = ruleset.info(agent.species);
+            Instance::new(Transform::identity().translate(corner), info.atlas_cell).tint(info.tint)
+        })
+        .collect()
+}
+
+/// `rps_simulator [SCENARIO]` runs a scenario, on a lattice if it sets one
+/// up, `rps_simulator --replay FILE` plays a replay back.
 fn main() {
-    let (ctx, event_loop) = ContextBuilder::new()
+    let exit = |e: anyhow::Error| -> ! {
+        eprintln!("{e:#}");
+        std::process::exit(1);
+    };
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (scenario, replay) = match args.as_slice() {
+        [] => (Scenario::default(), None),
+        [flag, filepath] if flag == "--replay" => {
+            let replay = Replay::load(filepath).unwrap_or_else(|e| exit(e));
+            (replay.scenario.clone(), Some(replay))
+        }
+        [filepath] => (Scenario::load(filepath).unwrap_or_else(|e| exit(e)), None),
+        _ => exit(anyhow::anyhow!(
+            "usage: rps_simulator [SCENARIO | --replay FILE]"
+        )),
+    };
+
+    let mut builder = ContextBuilder::new()
         .title("rps simulator")
-        .build()
-        .expect("Failed to create context!");
+        .size(scenario.width as u32, scenario.height as u32);
+    if let Some(replay) = &replay {
+        builder = builder.tick_rate(1.0 / replay.time_step);
+    }
+    let (ctx, event_loop) = builder.build().expect("Failed to create context!");
+
+    if replay.is_none() {
+        if let Some(lattice) = scenario.build_lattice().unwrap_or_else(|e| exit(e)) {
+            ctx.run(event_loop, move |ctx| {
+                LatticeSimulation::new(ctx, lattice, &scenario)
+            });
+        }
+    }
 
-    ctx.run(event_loop, Simulation::new);
+    let world = scenario
+        .world_builder()
+        .statistics(STATISTICS_INTERVAL)
+        .build()
+        .unwrap_or_else(|e| exit(e));
+    ctx.run(event_loop, move |ctx| {
+        Simulation::new(ctx, world, &scenario, replay)
+    });
 }
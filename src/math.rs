@@ -1,4 +1,7 @@
-use std::{f32::consts as f32, ops::Mul};
+use std::{
+    f32::consts as f32,
+    ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Rad(pub f32);
@@ -18,6 +21,136 @@ impl From<Rad> for Deg {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Self = Self::new(0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn dot(self, rhs: Self) -> f32 {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    /// Smallest of each coordinate.
+    pub fn min(self, rhs: Self) -> Self {
+        Self::new(self.x.min(rhs.x), self.y.min(rhs.y))
+    }
+
+    /// Largest of each coordinate.
+    pub fn max(self, rhs: Self) -> Self {
+        Self::new(self.x.max(rhs.x), self.y.max(rhs.y))
+    }
+
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
+    }
+
+    /// Returns the unit vector with the same direction, or zero if the length is zero.
+    pub fn normalize_or_zero(self) -> Self {
+        let length = self.length();
+        if length > 0.0 {
+            self * (1.0 / length)
+        } else {
+            Self::ZERO
+        }
+    }
+
+    /// Signed angle that rotates `self` onto `other`, counter-clockwise is positive.
+    ///
+    /// Like [`Vec2::rotate`] this goes through `libm` rather than the
+    /// platform's math library, so simulations give bit-identical results
+    /// on every machine.
+    pub fn angle_to(self, other: Self) -> Rad {
+        let cross = self.x * other.y - self.y * other.x;
+        Rad(libm::atan2f(cross, self.dot(other)))
+    }
+
+    pub fn rotate<A>(self, theta: A) -> Self
+    where
+        A: Into<Rad>,
+    {
+        let (sin_theta, cos_theta) = libm::sincosf(theta.into().0);
+
+        Self::new(
+            self.x * cos_theta - self.y * sin_theta,
+            self.x * sin_theta + self.y * cos_theta,
+        )
+    }
+}
+
+impl From<[f32; 2]> for Vec2 {
+    fn from(v: [f32; 2]) -> Self {
+        Self::new(v[0], v[1])
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(v: Vec2) -> Self {
+        [v.x, v.y]
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Self::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl MulAssign<f32> for Vec2 {
+    fn mul_assign(&mut self, rhs: f32) {
+        *self = *self * rhs;
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.x, -self.y)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Transform(pub [f32; 9]);
 
@@ -160,6 +293,33 @@ mod tests {
         assert!(epsilon_eq(EPSILON, deg(f32::FRAC_PI_6), 30.0));
     }
 
+    #[test]
+    fn vec2_arithmetic() {
+        let a = Vec2::new(3.0, 4.0);
+        let b = Vec2::new(1.0, -2.0);
+
+        assert_eq!(a + b, Vec2::new(4.0, 2.0));
+        assert_eq!(a - b, Vec2::new(2.0, 6.0));
+        assert_eq!(a * 2.0, Vec2::new(6.0, 8.0));
+        assert_eq!(a.dot(b), -5.0);
+        assert_eq!(a.length(), 5.0);
+        assert_eq!(a.normalize_or_zero(), Vec2::new(0.6, 0.8));
+        assert_eq!(Vec2::ZERO.normalize_or_zero(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn vec2_rotation() {
+        const EPSILON: f32 = 1e-6;
+
+        let x = Vec2::new(1.0, 0.0);
+        let y = x.rotate(Deg(90.0));
+
+        assert!(epsilon_eq(EPSILON, y.x, 0.0));
+        assert!(epsilon_eq(EPSILON, y.y, 1.0));
+        assert!(epsilon_eq(EPSILON, x.angle_to(y).0, f32::FRAC_PI_2));
+        assert!(epsilon_eq(EPSILON, y.angle_to(x).0, -f32::FRAC_PI_2));
+    }
+
     #[test]
     fn transform_mat3x3_multiplication() {
         const EPSILON: f32 = 1e-2;
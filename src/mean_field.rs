@@ -0,0 +1,400 @@
+use std::{io::Write, path::Path};
+
+use anyhow::{Context as _, Result};
+
+use crate::{FightModel, FightResults, Ruleset, Species, Statistics};
+
+/// Equations of a well-mixed population, where every agent is equally
+/// likely to meet every other one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MeanFieldModel {
+    /// Fractions `x` of the population, `x_i' = rate * x_i * (f_i - f)`
+    /// where `f_i = sum_j (B_ij - B_ji) * x_j` and `f = sum_i x_i * f_i` is
+    /// the mean. `rate` is the number of encounters per agent per second.
+    #[default]
+    Replicator,
+    /// Counts `N` of every species,
+    /// `N_i' = N_i * (rate * sum_j (efficiency * B_ij - B_ji) * N_j - mortality)`.
+    /// `rate` is the number of encounters per pair of agents per second,
+    /// winners turn `efficiency` of their prey into offspring and agents
+    /// die at `mortality` per second.
+    LotkaVolterra { efficiency: f64, mortality: f64 },
+}
+
+/// Mean-field prediction of the populations of a ruleset, integrated with a
+/// fourth order Runge-Kutta method whose step adapts to the tolerance.
+///
+/// `B_ij` is the chance that `i` converts, or eats, `j` when they meet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeanField {
+    len: usize,
+    /// `wins[i * len + j]` is `B_ij`.
+    wins: Vec<f64>,
+    pub model: MeanFieldModel,
+    pub rate: f64,
+    /// Largest error allowed per step, relative to the populations.
+    pub tolerance: f64,
+}
+
+impl MeanField {
+    /// Steps are never made shorter than this, in seconds.
+    pub const MIN_STEP: f64 = 1e-9;
+
+    /// Winners of the ruleset always convert their losers.
+    pub fn from_ruleset(ruleset: &Ruleset) -> Self {
+        let len = ruleset.len();
+        let mut wins = vec![0.0; len * len];
+        for a in ruleset.species() {
+            for b in ruleset.species() {
+                if ruleset.fight(a, b) == FightResults::Won {
+                    wins[a.index() * len + b.index()] = 1.0;
+                }
+            }
+        }
+
+        Self {
+            len,
+            wins,
+            model: MeanFieldModel::default(),
+            rate: 1.0,
+            tolerance: 1e-8,
+        }
+    }
+
+    /// Uses the odds of a probabilistic fight model, per contact.
+    pub fn from_fight(ruleset: &Ruleset, fight: &FightModel) -> Self {
+        let mut mean_field = Self::from_ruleset(ruleset);
+        if let Some(odds) = &fight.odds {
+            for a in ruleset.species() {
+                for b in ruleset.species() {
+                    mean_field.wins[a.index() * mean_field.len + b.index()] = odds.get(a, b) as f64;
+                }
+            }
+        }
+
+        mean_field
+    }
+
+    pub fn model(mut self, model: MeanFieldModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    pub fn rate(mut self, rate: f64) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Encounters per agent per second of agents moving at `speed` in
+    /// random directions, touching within `contact_radius`, with `density`
+    /// agents per unit of area.
+    pub fn encounter_rate(contact_radius: f64, speed: f64, density: f64) -> f64 {
+        // the mean relative speed of two such agents is 4 / pi times theirs
+        2.0 * contact_radius * speed * 4.0 / std::f64::consts::PI * density
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn wins(&self, a: usize, b: usize) -> f64 {
+        self.wins[a * self.len + b]
+    }
+
+    /// Time derivative of the state, fractions or counts depending on the
+    /// model.
+    pub fn derivative(&self, state: &[f64]) -> Vec<f64> {
+        let n = self.len;
+        match self.model {
+            MeanFieldModel::Replicator => {
+                let fitness = (0..n)
+                    .map(|i| {
+                        (0..n)
+                            .map(|j| (self.wins(i, j) - self.wins(j, i)) * state[j])
+                            .sum::<f64>()
+                    })
+                    .collect::<Vec<_>>();
+                let mean = state.iter().zip(&fitness).map(|(x, f)| x * f).sum::<f64>();
+
+                (0..n)
+                    .map(|i| self.rate * state[i] * (fitness[i] - mean))
+                    .collect()
+            }
+            MeanFieldModel::LotkaVolterra {
+                efficiency,
+                mortality,
+            } => (0..n)
+                .map(|i| {
+                    let gain = (0..n)
+                        .map(|j| (efficiency * self.wins(i, j) - self.wins(j, i)) * state[j])
+                        .sum::<f64>();
+                    state[i] * (self.rate * gain - mortality)
+                })
+                .collect(),
+        }
+    }
+
+    fn rk4(&self, state: &[f64], h: f64) -> Vec<f64> {
+        let offset = |k: &[f64], scale: f64| -> Vec<f64> {
+            state.iter().zip(k).map(|(y, k)| y + k * scale).collect()
+        };
+
+        let k1 = self.derivative(state);
+        let k2 = self.derivative(&offset(&k1, h / 2.0));
+        let k3 = self.derivative(&offset(&k2, h / 2.0));
+        let k4 = self.derivative(&offset(&k3, h));
+
+        (0..state.len())
+            .map(|i| state[i] + h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]))
+            .collect()
+    }
+
+    /// State at every one of the increasing `times`, starting from
+    /// `initial` at time 0.
+    ///
+    /// Every step is compared to two half steps, the step shrinks until
+    /// they agree within the tolerance and grows again while they do.
+    pub fn solve(&self, initial: &[f64], times: &[f64]) -> Vec<Vec<f64>> {
+        assert_eq!(initial.len(), self.len, "one value per species expected");
+
+        let mut state = initial.to_vec();
+        let mut time = 0.0;
+        let mut step = 0.01_f64;
+        let mut states = Vec::with_capacity(times.len());
+
+        for &target in times {
+            while target - time > Self::MIN_STEP {
+                let h = step.min(target - time);
+                let full = self.rk4(&state, h);
+                let half = self.rk4(&self.rk4(&state, h / 2.0), h / 2.0);
+
+                let scale = state.iter().fold(1.0_f64, |max, y| max.max(y.abs()));
+                let error = full
+                    .iter()
+                    .zip(&half)
+                    .fold(0.0_f64, |max, (a, b)| max.max((a - b).abs()))
+                    / 15.0
+                    / scale;
+
+                if error > self.tolerance && h > Self::MIN_STEP {
+                    step = (h * (0.9 * (self.tolerance / error).powf(0.25)).max(0.1))
+                        .max(Self::MIN_STEP);
+                    continue;
+                }
+
+                // Richardson extrapolation of the two estimates
+                state = half
+                    .iter()
+                    .zip(&full)
+                    .map(|(half, full)| half + (half - full) / 15.0)
+                    .collect();
+                time += h;
+                step = match error {
+                    0.0 => h * 2.0,
+                    _ => h * (0.9 * (self.tolerance / error).powf(0.2)).clamp(0.1, 2.0),
+                };
+            }
+            states.push(state.clone());
+        }
+
+        states
+    }
+
+    /// Prediction at the times of every sample of `statistics`, starting
+    /// from the populations of its first sample.
+    pub fn predict(&self, statistics: &Statistics) -> Prediction {
+        let samples = statistics.samples();
+        let Some(first) = samples.first() else {
+            return Prediction {
+                species: statistics.species().to_vec(),
+                samples: Vec::new(),
+            };
+        };
+
+        let counts = first.populations.iter().map(|&n| n as f64);
+        let total = counts.clone().sum::<f64>().max(1.0);
+        let initial = match self.model {
+            MeanFieldModel::Replicator => counts.map(|n| n / total).collect::<Vec<_>>(),
+            MeanFieldModel::LotkaVolterra { .. } => counts.collect(),
+        };
+        let times = samples
+            .iter()
+            .map(|sample| sample.elapsed - first.elapsed)
+            .collect::<Vec<_>>();
+
+        let samples = self
+            .solve(&initial, &times)
+            .into_iter()
+            .zip(samples)
+            .map(|(state, sample)| {
+                let populations = match self.model {
+                    MeanFieldModel::Replicator => state.iter().map(|x| x * total).collect(),
+                    MeanFieldModel::LotkaVolterra { .. } => state,
+                };
+                (sample.elapsed, populations)
+            })
+            .collect();
+
+        Prediction {
+            species: statistics.species().to_vec(),
+            samples,
+        }
+    }
+}
+
+/// Populations predicted by a [`MeanField`], sampled like the
+/// [`Statistics`] they were predicted for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prediction {
+    species: Vec<String>,
+    /// Time in seconds and expected number of agents of every species.
+    samples: Vec<(f64, Vec<f64>)>,
+}
+
+impl Prediction {
+    pub fn samples(&self) -> &[(f64, Vec<f64>)] {
+        &self.samples
+    }
+
+    /// Expected number of agents of `species` at every sample.
+    pub fn population(&self, species: Species) -> impl Iterator<Item = f64> + '_ {
+        self.samples
+            .iter()
+            .map(move |(_, populations)| populations[species.index()])
+    }
+
+    /// Root mean square difference between the predicted and the recorded
+    /// fractions of every species, from 0 for a perfect prediction up to 1.
+    pub fn deviation(&self, statistics: &Statistics) -> f64 {
+        let mut sum = 0.0;
+        let mut count = 0;
+        for ((_, predicted), sample) in self.samples.iter().zip(statistics.samples()) {
+            let predicted_total = predicted.iter().sum::<f64>().max(f64::MIN_POSITIVE);
+            let recorded_total = sample.populations.iter().sum::<usize>().max(1) as f64;
+            for (p, &r) in predicted.iter().zip(&sample.populations) {
+                let difference = p / predicted_total - r as f64 / recorded_total;
+                sum += difference * difference;
+                count += 1;
+            }
+        }
+
+        if count == 0 {
+            0.0
+        } else {
+            (sum / count as f64).sqrt()
+        }
+    }
+
+    /// Writes one row per sample, with a column per species named after it.
+    pub fn write_csv<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        write!(writer, "time")?;
+        for name in &self.species {
+            write!(writer, ",{name}")?;
+        }
+        writeln!(writer)?;
+
+        for (time, populations) in &self.samples {
+            write!(writer, "{time}")?;
+            for count in populations {
+                write!(writer, ",{count}")?;
+            }
+            writeln!(writer)?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn save_csv<P>(&self, filepath: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        let file = std::fs::File::create(filepath)
+            .with_context(|| format!("Could not create {}", filepath.display()))?;
+
+        self.write_csv(std::io::BufWriter::new(file))
+            .with_context(|| format!("Could not write the prediction to {}", filepath.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Shape, WorldBuilder};
+
+    #[test]
+    fn solutions_keep_the_invariants() {
+        let mean_field = MeanField::from_ruleset(&Ruleset::classic()).rate(2.0);
+        let initial = [0.5, 0.3, 0.2];
+        let states = mean_field.solve(&initial, &[1.0, 10.0, 50.0]);
+
+        // rock paper scissors orbits keep the product of the fractions
+        let product = |x: &[f64]| x.iter().product::<f64>();
+        for state in &states {
+            assert!((state.iter().sum::<f64>() - 1.0).abs() < 1e-6);
+            assert!((product(state) - product(&initial)).abs() < 1e-6);
+        }
+        assert!((states[0][0] - initial[0]).abs() > 0.01);
+
+        // conversions only move agents between species
+        let lotka_volterra = mean_field.model(MeanFieldModel::LotkaVolterra {
+            efficiency: 1.0,
+            mortality: 0.0,
+        });
+        let states = lotka_volterra.solve(&[50.0, 30.0, 20.0], &[5.0]);
+        assert!((states[0].iter().sum::<f64>() - 100.0).abs() < 1e-4);
+
+        let dying = lotka_volterra.model(MeanFieldModel::LotkaVolterra {
+            efficiency: 1.0,
+            mortality: 0.5,
+        });
+        assert!(
+            dying.solve(&[50.0, 30.0, 20.0], &[5.0])[0]
+                .iter()
+                .sum::<f64>()
+                < 100.0
+        );
+    }
+
+    #[test]
+    fn predictions_follow_the_statistics() {
+        let mut world = WorldBuilder::new()
+            .seed(1)
+            .statistics(30)
+            .population(Shape::Rock, 30)
+            .population(Shape::Paper, 20)
+            .population(Shape::Scissors, 10)
+            .build()
+            .unwrap();
+        for _ in 0..300 {
+            world.step(1.0 / 60.0);
+        }
+
+        let statistics = world.statistics().unwrap();
+        let prediction = MeanField::from_ruleset(world.ruleset())
+            .rate(0.5)
+            .predict(statistics);
+
+        assert_eq!(prediction.samples().len(), statistics.samples().len());
+        assert_eq!(prediction.samples()[0].1, [30.0, 20.0, 10.0]);
+        let deviation = prediction.deviation(statistics);
+        assert!((0.0..1.0).contains(&deviation));
+
+        let mut csv = Vec::new();
+        prediction.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("time,rock,paper,scissors\n0,30,20,10\n"));
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{ensure, Result};
+
+use crate::{Rng, Species};
+
+/// Spontaneous changes of species, happening regardless of any contact so
+/// extinct species can come back.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mutation {
+    /// Chance of every agent mutating on each tick.
+    pub rate: f32,
+    /// Species mutants turn into, `None` picks any other species with equal
+    /// odds.
+    pub matrix: Option<MutationMatrix>,
+}
+
+impl Mutation {
+    pub fn new(rate: f32) -> Self {
+        Self { rate, matrix: None }
+    }
+
+    pub fn matrix(mut self, matrix: MutationMatrix) -> Self {
+        self.matrix = Some(matrix);
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.rate > 0.0
+    }
+
+    /// Species a mutating agent of `species` turns into, out of `len`
+    /// species, or `None` if it can not turn into any other one.
+    pub fn pick(&self, rng: &mut Rng, species: Species, len: usize) -> Option<Species> {
+        match &self.matrix {
+            Some(matrix) => matrix.pick(rng, species),
+            None if len < 2 => None,
+            None => {
+                // skip over the species of the mutant
+                let other = rng.below(len - 1);
+                let other = if other >= species.index() {
+                    other + 1
+                } else {
+                    other
+                };
+                Some(Species(other as u8))
+            }
+        }
+    }
+}
+
+/// Relative weights of the species mutants of every species turn into.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MutationMatrix {
+    len: usize,
+    /// `weights[a * len + b]` is the weight of `a` mutating into `b`.
+    weights: Vec<f32>,
+}
+
+impl MutationMatrix {
+    /// Matrix where nobody mutates, for `len` species.
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            weights: vec![0.0; len * len],
+        }
+    }
+
+    /// Matrix where mutants turn into any other species with equal odds.
+    pub fn uniform(len: usize) -> Self {
+        let mut matrix = Self::new(len);
+        for a in 0..len {
+            for b in 0..len {
+                if a != b {
+                    matrix.weights[a * len + b] = 1.0;
+                }
+            }
+        }
+
+        matrix
+    }
+
+    /// Sets the weight of `a` mutating into `b`, fails for negative weights
+    /// and for mutations into the same species.
+    pub fn set(&mut self, a: Species, b: Species, weight: f32) -> Result<()> {
+        let (i, j) = (a.index(), b.index());
+        ensure!(i < self.len && j < self.len, "unknown species in {i} > {j}");
+        ensure!(i != j, "species {i} can not mutate into itself");
+        ensure!(
+            weight.is_finite() && weight >= 0.0,
+            "mutation weights must be positive, not {weight}"
+        );
+
+        self.weights[i * self.len + j] = weight;
+        Ok(())
+    }
+
+    pub fn get(&self, a: Species, b: Species) -> f32 {
+        self.weights[a.index() * self.len + b.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Draws the species a mutant of `species` turns into, `None` if all
+    /// its weights are zero.
+    pub fn pick(&self, rng: &mut Rng, species: Species) -> Option<Species> {
+        let row = &self.weights[species.index() * self.len..][..self.len];
+        let total = row.iter().sum::<f32>();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut roll = rng.next_f32() * total;
+        for (b, &weight) in row.iter().enumerate() {
+            if weight > 0.0 && roll < weight {
+                return Some(Species(b as u8));
+            }
+            roll -= weight;
+        }
+
+        // rounding left the roll past the last weight
+        row.iter()
+            .rposition(|&weight| weight > 0.0)
+            .map(|b| Species(b as u8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutants_follow_the_matrix() {
+        let mut rng = Rng::new(5);
+        let uniform = Mutation::new(0.1);
+        for _ in 0..100 {
+            assert_ne!(uniform.pick(&mut rng, Species(1), 3), Some(Species(1)));
+        }
+        assert_eq!(uniform.pick(&mut rng, Species(0), 1), None);
+
+        let mut matrix = MutationMatrix::new(3);
+        matrix.set(Species(0), Species(2), 0.5).unwrap();
+        assert!(matrix.set(Species(0), Species(0), 1.0).is_err());
+        assert!(matrix.set(Species(0), Species(1), -1.0).is_err());
+
+        let biased = Mutation::new(0.1).matrix(matrix);
+        for _ in 0..100 {
+            assert_eq!(biased.pick(&mut rng, Species(0), 3), Some(Species(2)));
+        }
+        assert_eq!(biased.pick(&mut rng, Species(1), 3), None);
+    }
+}
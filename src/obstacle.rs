@@ -0,0 +1,178 @@
+use crate::Vec2;
+
+/// Static shape agents can neither enter nor, optionally, see through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Obstacle {
+    Rect { min: Vec2, max: Vec2 },
+    Circle { center: Vec2, radius: f32 },
+}
+
+impl Obstacle {
+    /// Rectangle with a corner at `x, y`, negative sizes extend it to the
+    /// left or up.
+    pub fn rect(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Obstacle::corners(Vec2::new(x, y), Vec2::new(x + width, y + height))
+    }
+
+    /// Rectangle between two opposite corners, in any order.
+    pub fn corners(a: Vec2, b: Vec2) -> Self {
+        Obstacle::Rect {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn circle(x: f32, y: f32, radius: f32) -> Self {
+        Obstacle::Circle {
+            center: Vec2::new(x, y),
+            radius,
+        }
+    }
+
+    /// Whether `point` is strictly inside, points on the edge are outside.
+    pub fn contains(&self, point: Vec2) -> bool {
+        match *self {
+            Obstacle::Rect { min, max } => {
+                min.x < point.x && point.x < max.x && min.y < point.y && point.y < max.y
+            }
+            Obstacle::Circle { center, radius } => {
+                (point - center).length_squared() < radius * radius
+            }
+        }
+    }
+
+    /// Point of the edge closest to `point`, for points outside the obstacle.
+    pub fn closest_point(&self, point: Vec2) -> Vec2 {
+        match *self {
+            Obstacle::Rect { min, max } => {
+                Vec2::new(point.x.clamp(min.x, max.x), point.y.clamp(min.y, max.y))
+            }
+            Obstacle::Circle { center, radius } => {
+                center + (point - center).normalize_or_zero() * radius
+            }
+        }
+    }
+
+    /// Moves a point that ended up inside back onto the closest edge and
+    /// reflects its velocity, returns `false` if the point was outside.
+    pub fn push_out(&self, position: &mut Vec2, velocity: &mut Vec2) -> bool {
+        if !self.contains(*position) {
+            return false;
+        }
+
+        let (surface, normal) = match *self {
+            Obstacle::Rect { min, max } => {
+                let p = *position;
+                let exits = [
+                    (p.x - min.x, Vec2::new(min.x, p.y), Vec2::new(-1.0, 0.0)),
+                    (max.x - p.x, Vec2::new(max.x, p.y), Vec2::new(1.0, 0.0)),
+                    (p.y - min.y, Vec2::new(p.x, min.y), Vec2::new(0.0, -1.0)),
+                    (max.y - p.y, Vec2::new(p.x, max.y), Vec2::new(0.0, 1.0)),
+                ];
+                let (_, surface, normal) = exits
+                    .into_iter()
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+                    .unwrap();
+                (surface, normal)
+            }
+            Obstacle::Circle { center, radius } => {
+                let mut normal = (*position - center).normalize_or_zero();
+                if normal == Vec2::ZERO {
+                    normal = Vec2::new(1.0, 0.0);
+                }
+                (center + normal * radius, normal)
+            }
+        };
+
+        *position = surface;
+        let approach = velocity.dot(normal);
+        if approach < 0.0 {
+            *velocity -= normal * (2.0 * approach);
+        }
+
+        true
+    }
+
+    /// Whether the segment from `a` to `b` crosses the obstacle.
+    pub fn blocks(&self, a: Vec2, b: Vec2) -> bool {
+        let d = b - a;
+        match *self {
+            Obstacle::Rect { min, max } => {
+                // clip the segment against both slabs
+                let (mut enter, mut exit) = (0.0_f32, 1.0_f32);
+                for (start, delta, low, high) in
+                    [(a.x, d.x, min.x, max.x), (a.y, d.y, min.y, max.y)]
+                {
+                    if delta == 0.0 {
+                        if start <= low || start >= high {
+                            return false;
+                        }
+                        continue;
+                    }
+
+                    let (t0, t1) = ((low - start) / delta, (high - start) / delta);
+                    enter = enter.max(t0.min(t1));
+                    exit = exit.min(t0.max(t1));
+                }
+                enter < exit
+            }
+            Obstacle::Circle { center, radius } => {
+                let length_squared = d.length_squared();
+                let t = if length_squared > 0.0 {
+                    ((center - a).dot(d) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (a + d * t - center).length_squared() < radius * radius
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_are_pushed_out_and_bounce() {
+        let wall = Obstacle::rect(10.0, 0.0, 10.0, 100.0);
+        let mut position = Vec2::new(11.0, 50.0);
+        let mut velocity = Vec2::new(20.0, 5.0);
+
+        assert!(wall.push_out(&mut position, &mut velocity));
+        assert_eq!(position, Vec2::new(10.0, 50.0));
+        assert_eq!(velocity, Vec2::new(-20.0, 5.0));
+
+        let pillar = Obstacle::circle(0.0, 0.0, 10.0);
+        let mut position = Vec2::new(0.0, 5.0);
+        let mut velocity = Vec2::new(0.0, -10.0);
+
+        assert!(pillar.push_out(&mut position, &mut velocity));
+        assert_eq!(position, Vec2::new(0.0, 10.0));
+        assert_eq!(velocity, Vec2::new(0.0, 10.0));
+        assert!(!pillar.push_out(&mut position, &mut velocity));
+    }
+
+    #[test]
+    fn rects_accept_negative_sizes() {
+        let rect = Obstacle::rect(100.0, 100.0, -20.0, 50.0);
+
+        assert_eq!(rect, Obstacle::rect(80.0, 100.0, 20.0, 50.0));
+        assert_eq!(
+            rect.closest_point(Vec2::new(0.0, 0.0)),
+            Vec2::new(80.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn segments_are_blocked() {
+        let wall = Obstacle::rect(10.0, 0.0, 10.0, 100.0);
+        let pillar = Obstacle::circle(50.0, 50.0, 10.0);
+
+        assert!(wall.blocks(Vec2::new(0.0, 50.0), Vec2::new(30.0, 60.0)));
+        assert!(!wall.blocks(Vec2::new(0.0, 50.0), Vec2::new(5.0, 200.0)));
+        assert!(!wall.blocks(Vec2::new(0.0, 101.0), Vec2::new(30.0, 101.0)));
+        assert!(pillar.blocks(Vec2::new(0.0, 45.0), Vec2::new(100.0, 55.0)));
+        assert!(!pillar.blocks(Vec2::new(0.0, 70.0), Vec2::new(100.0, 70.0)));
+    }
+}
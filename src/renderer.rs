@@ -90,7 +90,7 @@ impl Renderer {
                 entry_point: "fragment",
                 targets: &[Some(wgpu::ColorTargetState {
                     format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -137,6 +137,14 @@ impl Renderer {
             .resize(&self.queue, new_size.width, new_size.height);
     }
 
+    pub fn set_view(&mut self, view: Option<(f32, f32)>) {
+        self.projection.set_view(&self.queue, view);
+    }
+
+    pub fn unproject(&self, x: f32, y: f32) -> [f32; 2] {
+        self.projection.unproject(x, y)
+    }
+
     pub fn clear(
         &mut self,
         view: &wgpu::TextureView,
@@ -215,6 +223,11 @@ impl Renderer {
         match command {
             DrawCommand::Clear(color) => self.clear(&view, &mut encoder, color),
             DrawCommand::DrawMesh(command) => self.draw_mesh(&view, &mut encoder, command),
+            DrawCommand::DrawMeshes(commands) => {
+                for command in commands {
+                    self.draw_mesh(&view, &mut encoder, command);
+                }
+            }
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
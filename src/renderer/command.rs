@@ -4,6 +4,8 @@ use crate::{InstanceBuffer, Mesh, Texture};
 pub enum DrawCommand<'a> {
     Clear(wgpu::Color),
     DrawMesh(DrawMeshCommand<'a>),
+    /// Draws the meshes in order, later ones on top.
+    DrawMeshes(Vec<DrawMeshCommand<'a>>),
 }
 
 #[derive(Debug)]
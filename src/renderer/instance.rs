@@ -8,11 +8,13 @@ use wgpu::util::DeviceExt;
 pub struct Instance {
     model: [f32; 9],
     uv_index: [u32; 2],
+    tint: [f32; 4],
 }
 
 impl Instance {
-    pub const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 4] =
-        wgpu::vertex_attr_array![2 => Float32x3, 3 => Float32x3, 4 => Float32x3, 5 => Uint32x2];
+    pub const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        2 => Float32x3, 3 => Float32x3, 4 => Float32x3, 5 => Uint32x2, 6 => Float32x4
+    ];
 
     pub const fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
@@ -29,8 +31,15 @@ impl Instance {
         Self {
             model: model.into(),
             uv_index,
+            tint: [1.0; 4],
         }
     }
+
+    /// Multiplies the texture with a color, white leaves it unchanged.
+    pub fn tint(mut self, [r, g, b]: [u8; 3]) -> Self {
+        self.tint = [r, g, b, 255].map(|channel| channel as f32 / 255.0);
+        self
+    }
 }
 
 #[derive(Debug)]
@@ -73,6 +73,48 @@ impl Mesh {
         }
     }
 
+    /// Circle approximated by `segments` triangles, its bounding square
+    /// starts at the origin like [`Mesh::rect`].
+    pub fn circle(device: &wgpu::Device, radius: f32, segments: u16) -> Self {
+        let segments = segments.max(3);
+        let center = Vertex {
+            position: [radius, radius],
+            uv: [0.5, 0.5],
+        };
+        let rim = (0..segments).map(|i| {
+            let theta = std::f32::consts::TAU * i as f32 / segments as f32;
+            let (sin, cos) = theta.sin_cos();
+            Vertex {
+                position: [radius + radius * cos, radius + radius * sin],
+                uv: [0.5 + 0.5 * cos, 0.5 + 0.5 * sin],
+            }
+        });
+        let buffer = std::iter::once(center).chain(rim).collect::<Vec<_>>();
+
+        // same winding as the rectangle, so the back faces are culled alike
+        let indices = (1..=segments)
+            .flat_map(|i| [0, i % segments + 1, i])
+            .collect::<Vec<u16>>();
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh vertex buffer"),
+            contents: bytemuck::cast_slice(&buffer),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh index buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+        }
+    }
+
     pub fn vertex_buffer(&self) -> &wgpu::Buffer {
         &self.vertex_buffer
     }
@@ -3,13 +3,15 @@ use wgpu::util::DeviceExt as _;
 #[derive(Debug)]
 pub struct Projection {
     data: [f32; 16],
+    window: (u32, u32),
+    view: Option<(f32, f32)>,
     buffer: wgpu::Buffer,
     bind_group: wgpu::BindGroup,
 }
 
 impl Projection {
     pub fn new(device: &wgpu::Device, width: u32, height: u32) -> (Self, wgpu::BindGroupLayout) {
-        let data = Self::create_projection_matrix(width, height);
+        let data = Self::create_projection_matrix(width, height, None);
 
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Projection buffer"),
@@ -43,6 +45,8 @@ impl Projection {
         (
             Self {
                 data,
+                window: (width, height),
+                view: None,
                 buffer,
                 bind_group,
             },
@@ -51,18 +55,53 @@ impl Projection {
     }
 
     pub fn resize(&mut self, queue: &wgpu::Queue, width: u32, height: u32) {
-        self.data = Self::create_projection_matrix(width, height);
+        self.window = (width, height);
+        self.update(queue);
+    }
+
+    /// Maps the `[0, width] x [0, height]` world rectangle onto the window,
+    /// keeping its aspect ratio. `None` maps world units to pixels.
+    pub fn set_view(&mut self, queue: &wgpu::Queue, view: Option<(f32, f32)>) {
+        self.view = view;
+        self.update(queue);
+    }
+
+    fn update(&mut self, queue: &wgpu::Queue) {
+        let (width, height) = self.window;
+        self.data = Self::create_projection_matrix(width, height, self.view);
 
         queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.data));
     }
 
+    /// World coordinates under a point of the window, in pixels.
+    pub fn unproject(&self, x: f32, y: f32) -> [f32; 2] {
+        let (width, height) = self.window;
+        let ndc_x = 2.0 * x / width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height as f32;
+
+        [
+            (ndc_x - self.data[12]) / self.data[0],
+            (ndc_y - self.data[13]) / self.data[5],
+        ]
+    }
+
     pub fn bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
 
-    fn create_projection_matrix(width: u32, height: u32) -> [f32; 16] {
-        let (l, r) = (0.0, width as f32);
-        let (t, b) = (0.0, height as f32);
+    fn create_projection_matrix(width: u32, height: u32, view: Option<(f32, f32)>) -> [f32; 16] {
+        let (width, height) = (width as f32, height as f32);
+        let (view_width, view_height) = view.unwrap_or((width, height));
+
+        // grow the view along one axis so it has the aspect ratio of the
+        // window, centering the world in the resulting letterbox
+        let scale = (width / view_width).min(height / view_height);
+        let (margin_x, margin_y) = (
+            (width / scale - view_width) / 2.0,
+            (height / scale - view_height) / 2.0,
+        );
+        let (l, r) = (-margin_x, view_width + margin_x);
+        let (t, b) = (-margin_y, view_height + margin_y);
 
         #[rustfmt::skip]
         let mut matrix = [
@@ -0,0 +1,383 @@
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context as _, Result};
+
+use crate::{
+    encoding::{Reader, Writer},
+    Agent, Finish, Scenario, SpeciesScenario, World,
+};
+
+const MAGIC: &[u8; 4] = b"RPSR";
+
+/// Bumped whenever the layout changes, older replays are rejected.
+pub const REPLAY_VERSION: u32 = 10;
+
+/// A user intervention, the only thing besides the seed that changes a run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Spawn(Agent),
+    /// Removes the agent at this index.
+    Remove(usize),
+    Pause,
+    Resume,
+    StepOnce,
+    Reset,
+}
+
+impl Input {
+    /// Applies the input, `dt` is the duration of [`Input::StepOnce`].
+    pub fn apply(self, world: &mut World, dt: f32) -> Option<Finish> {
+        match self {
+            Input::Spawn(agent) => world.spawn(agent),
+            Input::Remove(index) if index < world.agents().len() => {
+                world.remove(index);
+            }
+            Input::Remove(_) => {}
+            Input::Pause => world.pause(),
+            Input::Resume => world.resume(),
+            Input::StepOnce => return world.step_once(dt),
+            Input::Reset => world.reset(),
+        }
+
+        None
+    }
+}
+
+/// Everything needed to reproduce a run: the scenario, which includes the
+/// seed, and the inputs applied along the way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub scenario: Scenario,
+    /// Duration of every step, in seconds.
+    pub time_step: f32,
+    /// Inputs and the number of frames played before each of them.
+    pub inputs: Vec<(u64, Input)>,
+    /// Number of frames, each one steps the world unless it is paused.
+    pub frames: u64,
+    /// [`World::checksum`] at the end of the run.
+    pub checksum: u64,
+}
+
+impl Replay {
+    pub fn build_world(&self) -> Result<World> {
+        self.scenario.build_world()
+    }
+
+    /// Plays the whole replay without a window.
+    pub fn play(&self) -> Result<World> {
+        let mut world = self.build_world()?;
+        let mut player = ReplayPlayer::new(self.clone());
+        while !player.is_done() {
+            player.step(&mut world);
+        }
+
+        Ok(world)
+    }
+
+    /// Plays the replay and checks it still ends with the recorded checksum.
+    pub fn verify(&self) -> Result<World> {
+        let world = self.play()?;
+        ensure!(
+            world.checksum() == self.checksum,
+            "the replay diverged, checksum {:016x} instead of {:016x}",
+            world.checksum(),
+            self.checksum
+        );
+
+        Ok(world)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::new());
+        w.0.extend_from_slice(MAGIC);
+        w.u32(REPLAY_VERSION);
+
+        let scenario = &self.scenario;
+        w.u64(scenario.seed);
+        w.f32(scenario.width);
+        w.f32(scenario.height);
+        w.boundary(scenario.boundary);
+        w.f32(scenario.jitter);
+        w.f32(scenario.contact_radius);
+        w.stalemate(&scenario.stalemate);
+        w.obstacles(&scenario.obstacles);
+        w.u8(scenario.line_of_sight as u8);
+        w.ruleset(&scenario.ruleset);
+        w.spawn_pattern(&scenario.spawn);
+        w.fight(&scenario.fight);
+        w.mutation(&scenario.mutation);
+        w.ecology(scenario.ecology.as_ref());
+        w.lattice(scenario.lattice.as_ref());
+        for species in &scenario.species {
+            w.u64(species.count as u64);
+            w.steering(&species.steering);
+        }
+
+        w.f32(self.time_step);
+        w.u64(self.frames);
+        w.u64(self.checksum);
+        w.u32(self.inputs.len() as u32);
+        for (frame, input) in &self.inputs {
+            w.u64(*frame);
+            match input {
+                Input::Spawn(agent) => {
+                    w.u8(0);
+                    w.agent(agent);
+                }
+                Input::Remove(index) => {
+                    w.u8(1);
+                    w.u32(*index as u32);
+                }
+                Input::Pause => w.u8(2),
+                Input::Resume => w.u8(3),
+                Input::StepOnce => w.u8(4),
+                Input::Reset => w.u8(5),
+            }
+        }
+
+        w.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut r = Reader(bytes);
+        ensure!(r.take(4)? == MAGIC, "not a replay");
+        let version = r.u32()?;
+        ensure!(
+            version == REPLAY_VERSION,
+            "unsupported replay version {version}, expected {REPLAY_VERSION}"
+        );
+
+        let (seed, width, height) = (r.u64()?, r.f32()?, r.f32()?);
+        let (boundary, jitter, contact_radius) = (r.boundary()?, r.f32()?, r.f32()?);
+        let stalemate = r.stalemate()?;
+        let (obstacles, line_of_sight) = (r.obstacles()?, r.u8()? != 0);
+        let ruleset = r.ruleset()?;
+        let spawn = r.spawn_pattern(ruleset.len())?;
+        let fight = r.fight(ruleset.len())?;
+        let mutation = r.mutation(ruleset.len())?;
+        let ecology = r.ecology()?;
+        let lattice = r.lattice()?;
+        let species = ruleset
+            .species()
+            .map(|_| {
+                Ok(SpeciesScenario {
+                    count: r.u64()? as usize,
+                    steering: r.steering()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let len = ruleset.len();
+        let scenario = Scenario {
+            seed,
+            width,
+            height,
+            boundary,
+            jitter,
+            contact_radius,
+            stalemate,
+            obstacles,
+            line_of_sight,
+            spawn,
+            fight,
+            mutation,
+            ecology,
+            lattice,
+            ruleset,
+            species,
+        };
+
+        let (time_step, frames, checksum) = (r.f32()?, r.u64()?, r.u64()?);
+        let count = r.u32()? as usize;
+        let mut inputs = Vec::with_capacity(count.min(r.0.len()));
+        for _ in 0..count {
+            let frame = r.u64()?;
+            let input = match r.u8()? {
+                0 => Input::Spawn(r.agent(len)?),
+                1 => Input::Remove(r.u32()? as usize),
+                2 => Input::Pause,
+                3 => Input::Resume,
+                4 => Input::StepOnce,
+                5 => Input::Reset,
+                n => bail!("invalid input {n}"),
+            };
+            inputs.push((frame, input));
+        }
+        ensure!(r.0.is_empty(), "trailing bytes after the replay");
+        ensure!(
+            inputs.windows(2).all(|pair| pair[0].0 <= pair[1].0),
+            "the inputs are out of order"
+        );
+
+        Ok(Self {
+            scenario,
+            time_step,
+            inputs,
+            frames,
+            checksum,
+        })
+    }
+
+    pub fn save<P>(&self, filepath: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        std::fs::write(filepath, self.to_bytes())
+            .with_context(|| format!("Could not write replay {}", filepath.display()))
+    }
+
+    pub fn load<P>(filepath: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        let bytes = std::fs::read(filepath)
+            .with_context(|| format!("Could not read replay {}", filepath.display()))?;
+
+        Self::from_bytes(&bytes).with_context(|| format!("Invalid replay {}", filepath.display()))
+    }
+}
+
+/// Drives a world built from a scenario while logging the inputs applied
+/// to it, every frame of the run goes through [`Recorder::step`].
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    replay: Replay,
+}
+
+impl Recorder {
+    pub fn new(scenario: Scenario, time_step: f32) -> Self {
+        Self {
+            replay: Replay {
+                scenario,
+                time_step,
+                inputs: Vec::new(),
+                frames: 0,
+                checksum: 0,
+            },
+        }
+    }
+
+    pub fn input(&mut self, world: &mut World, input: Input) -> Option<Finish> {
+        self.replay.inputs.push((self.replay.frames, input));
+        input.apply(world, self.replay.time_step)
+    }
+
+    pub fn step(&mut self, world: &mut World) -> Option<Finish> {
+        self.replay.frames += 1;
+        world.step(self.replay.time_step)
+    }
+
+    /// The replay of the run so far, ending in the current state of `world`.
+    pub fn replay(&self, world: &World) -> Replay {
+        Replay {
+            checksum: world.checksum(),
+            ..self.replay.clone()
+        }
+    }
+}
+
+/// Plays a [`Replay`] back one frame at a time.
+#[derive(Debug, Clone)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    frame: u64,
+    next_input: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            frame: 0,
+            next_input: 0,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Applies the inputs due before the current frame then plays it, the
+    /// last call only applies the inputs logged after the final frame.
+    pub fn step(&mut self, world: &mut World) -> Option<Finish> {
+        let mut finish = None;
+        while let Some(&(frame, input)) = self.replay.inputs.get(self.next_input) {
+            if frame > self.frame {
+                break;
+            }
+            finish = finish.or(input.apply(world, self.replay.time_step));
+            self.next_input += 1;
+        }
+
+        if self.frame < self.replay.frames {
+            finish = finish.or(world.step(self.replay.time_step));
+        }
+        self.frame += 1;
+
+        finish
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.frame > self.replay.frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ruleset, SpawnPattern, Species};
+
+    fn record() -> Replay {
+        let scenario = Scenario {
+            seed: 11,
+            spawn: SpawnPattern::Clusters { spread: 60.0 },
+            ..Scenario::from_ruleset(Ruleset::rpsls(), 10)
+        };
+        let mut world = scenario.build_world().unwrap();
+        let mut recorder = Recorder::new(scenario, 1.0 / 60.0);
+
+        for frame in 0..600 {
+            match frame {
+                100 => {
+                    let agent = Agent::new([320.0, 240.0], [60.0, 0.0], Species(3));
+                    recorder.input(&mut world, Input::Spawn(agent));
+                }
+                200 => {
+                    recorder.input(&mut world, Input::Remove(4));
+                }
+                300 => {
+                    recorder.input(&mut world, Input::Pause);
+                }
+                350 => {
+                    recorder.input(&mut world, Input::StepOnce);
+                    recorder.input(&mut world, Input::Resume);
+                }
+                _ => {}
+            }
+            recorder.step(&mut world);
+        }
+
+        recorder.replay(&world)
+    }
+
+    #[test]
+    fn replays_reproduce_the_recorded_run() {
+        let replay = record();
+        assert_eq!(replay.inputs.len(), 5);
+
+        let restored = Replay::from_bytes(&replay.to_bytes()).unwrap();
+        assert_eq!(restored, replay);
+
+        let world = restored.verify().unwrap();
+        assert_eq!(world.tick(), 600 - 49);
+    }
+
+    #[test]
+    fn verification_catches_divergence() {
+        let mut replay = record();
+        replay.inputs.remove(1);
+
+        assert!(replay.verify().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::Vec2;
+
+/// Seedable xoshiro256** generator.
+///
+/// Only integer arithmetic is used to advance the state so a given seed
+/// produces the same sequence on every platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // expand the seed with splitmix64, as recommended by the xoshiro authors
+        let mut x = seed;
+        let mut state = [0; 4];
+        for s in &mut state {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *s = z ^ (z >> 31);
+        }
+
+        Self { state }
+    }
+
+    /// Resumes a generator from a state previously returned by [`Rng::state`].
+    pub fn from_state(state: [u64; 4]) -> Self {
+        Self { state }
+    }
+
+    pub fn state(&self) -> [u64; 4] {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    /// Uniformly distributed float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 * (1.0 / (1u64 << 24) as f32)
+    }
+
+    /// Uniformly distributed float in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// Uniformly distributed integer in `[0, n)`, `n` must not be zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "Rng::below called with an empty range");
+
+        let n = n as u64;
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return (x % n) as usize;
+            }
+        }
+    }
+
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    /// Random direction, picked by rejection sampling to avoid trigonometry.
+    pub fn unit_vector(&mut self) -> Vec2 {
+        loop {
+            let v = Vec2::new(self.range(-1.0, 1.0), self.range(-1.0, 1.0));
+            let length_squared = v.length_squared();
+            if length_squared > 1e-4 && length_squared <= 1.0 {
+                return v * (1.0 / length_squared.sqrt());
+            }
+        }
+    }
+
+    /// Approximately standard normal, the sum of twelve uniform floats so
+    /// no transcendental function is involved.
+    pub fn normal(&mut self) -> f32 {
+        (0..12).map(|_| self.next_f32()).sum::<f32>() - 6.0
+    }
+
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            slice.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+
+        let xs = (0..16).map(|_| a.next_u64()).collect::<Vec<_>>();
+        let ys = (0..16).map(|_| b.next_u64()).collect::<Vec<_>>();
+        let zs = (0..16).map(|_| c.next_u64()).collect::<Vec<_>>();
+
+        assert_eq!(xs, ys);
+        assert_ne!(xs, zs);
+    }
+
+    #[test]
+    fn samples_stay_in_range() {
+        let mut rng = Rng::new(7);
+
+        for _ in 0..1000 {
+            let x = rng.next_f32();
+            assert!((0.0..1.0).contains(&x));
+            assert!(rng.below(3) < 3);
+            assert!((rng.unit_vector().length() - 1.0).abs() < 1e-5);
+        }
+    }
+}
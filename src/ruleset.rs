@@ -0,0 +1,353 @@
+use anyhow::{anyhow, ensure, Result};
+
+use crate::{FightResults, Shape};
+
+/// Index of a species within a [`Ruleset`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Species(pub u8);
+
+impl Species {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl From<Shape> for Species {
+    fn from(shape: Shape) -> Self {
+        Self(shape as u8)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeciesInfo {
+    pub name: String,
+    /// Cell of the texture atlas used to draw the species.
+    pub atlas_cell: [u32; 2],
+    /// Color the sprite is multiplied with, white keeps it as drawn.
+    pub tint: [u8; 3],
+}
+
+impl SpeciesInfo {
+    /// Sprites in the atlas, one per classic shape.
+    pub const ATLAS_CELLS: usize = 3;
+
+    pub fn new<S>(name: S, atlas_cell: [u32; 2]) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            atlas_cell,
+            tint: [255; 3],
+        }
+    }
+
+    /// Species number `index` of a ruleset, the atlas sprites are reused
+    /// with a different tint every time they run out so no two species
+    /// look the same.
+    pub fn nth<S>(index: usize, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let round = index / Self::ATLAS_CELLS;
+        let tint = if round == 0 {
+            [255; 3]
+        } else {
+            // golden ratio steps keep consecutive hues far apart
+            let hue = ((round - 1) as f32 * 0.618_034).fract() * 6.0;
+            let channel = |offset: f32| {
+                let distance = ((hue - offset).rem_euclid(6.0) - 3.0).abs();
+                // pastel, so the sprites stay readable
+                (128.0 + (distance - 1.0).clamp(0.0, 1.0) * 127.0) as u8
+            };
+            [channel(0.0), channel(2.0), channel(4.0)]
+        };
+
+        Self {
+            tint,
+            ..Self::new(name, [(index % Self::ATLAS_CELLS) as u32, 0])
+        }
+    }
+
+    pub fn tint(mut self, tint: [u8; 3]) -> Self {
+        self.tint = tint;
+        self
+    }
+}
+
+/// A set of species and the dominance relation between them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ruleset {
+    species: Vec<SpeciesInfo>,
+    /// `outcomes[a * len + b]` is the result of `a` fighting `b`.
+    outcomes: Vec<FightResults>,
+}
+
+impl Ruleset {
+    pub const MAX_SPECIES: usize = u8::MAX as usize + 1;
+
+    /// Creates a ruleset where every fight is a tie.
+    pub fn new(species: Vec<SpeciesInfo>) -> Result<Self> {
+        ensure!(!species.is_empty(), "a ruleset needs at least one species");
+        ensure!(
+            species.len() <= Self::MAX_SPECIES,
+            "a ruleset can hold at most {} species",
+            Self::MAX_SPECIES
+        );
+
+        let len = species.len();
+        Ok(Self {
+            species,
+            outcomes: vec![FightResults::Tied; len * len],
+        })
+    }
+
+    /// Creates a ruleset from a tournament graph, every `(winner, loser)`
+    /// edge means the first species beats the second.
+    pub fn from_tournament(
+        species: Vec<SpeciesInfo>,
+        edges: &[(Species, Species)],
+    ) -> Result<Self> {
+        let mut ruleset = Self::new(species)?;
+        for &(winner, loser) in edges {
+            ruleset.set_beats(winner, loser)?;
+        }
+
+        Ok(ruleset)
+    }
+
+    /// Creates a ruleset from a payoff matrix, `a` beats `b` when
+    /// `payoff[a][b]` is greater than `payoff[b][a]`.
+    pub fn from_payoff_matrix(species: Vec<SpeciesInfo>, payoff: &[Vec<f32>]) -> Result<Self> {
+        let mut ruleset = Self::new(species)?;
+        let len = ruleset.len();
+
+        ensure!(
+            payoff.len() == len && payoff.iter().all(|row| row.len() == len),
+            "the payoff matrix must be {len}x{len}"
+        );
+
+        for (a, row) in payoff.iter().enumerate() {
+            for (b, &value) in row.iter().enumerate() {
+                ruleset.outcomes[a * len + b] = match value.partial_cmp(&payoff[b][a]) {
+                    Some(std::cmp::Ordering::Greater) => FightResults::Won,
+                    Some(std::cmp::Ordering::Less) => FightResults::Lost,
+                    _ => FightResults::Tied,
+                };
+            }
+        }
+
+        Ok(ruleset)
+    }
+
+    /// Rock, paper and scissors, matching [`Shape::fight`].
+    pub fn classic() -> Self {
+        let species = ["rock", "paper", "scissors"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| SpeciesInfo::nth(i, name))
+            .collect();
+
+        Self::cyclic(species, &[1])
+    }
+
+    /// Rock, paper, scissors, Spock and lizard.
+    pub fn rpsls() -> Self {
+        let species = ["rock", "paper", "scissors", "spock", "lizard"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| SpeciesInfo::nth(i, name))
+            .collect();
+
+        Self::cyclic(species, &[1, 3])
+    }
+
+    /// Balanced tournament of `n` species where each one beats the `(n - 1) / 2`
+    /// species before it, `n` must be odd.
+    pub fn balanced(n: usize) -> Result<Self> {
+        ensure!(
+            n % 2 == 1,
+            "balanced rulesets need an odd number of species"
+        );
+        ensure!(
+            n <= Self::MAX_SPECIES,
+            "a ruleset can hold at most {} species",
+            Self::MAX_SPECIES
+        );
+
+        let species = (0..n)
+            .map(|i| SpeciesInfo::nth(i, format!("species{i}")))
+            .collect();
+        let offsets = (1..=(n - 1) / 2).collect::<Vec<_>>();
+
+        Ok(Self::cyclic(species, &offsets))
+    }
+
+    /// Looks a preset up by name: `classic`, `rpsls` or `balanced-N`.
+    pub fn preset(name: &str) -> Result<Self> {
+        match name {
+            "classic" => Ok(Self::classic()),
+            "rpsls" => Ok(Self::rpsls()),
+            _ => {
+                let n = name
+                    .strip_prefix("balanced-")
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| anyhow!("unknown ruleset preset {name:?}"))?;
+                Self::balanced(n)
+            }
+        }
+    }
+
+    /// Species `i` beats species `i - offset` for every offset.
+    fn cyclic(species: Vec<SpeciesInfo>, offsets: &[usize]) -> Self {
+        let mut ruleset = Self::new(species).expect("presets have a valid species count");
+        let len = ruleset.len();
+
+        for winner in 0..len {
+            for offset in offsets {
+                let loser = (winner + len - offset) % len;
+                ruleset
+                    .set_beats(Species(winner as u8), Species(loser as u8))
+                    .expect("presets are consistent");
+            }
+        }
+
+        ruleset
+    }
+
+    /// Makes `winner` beat `loser`, fails if that contradicts an earlier rule.
+    pub fn set_beats(&mut self, winner: Species, loser: Species) -> Result<()> {
+        let len = self.len();
+        let (w, l) = (winner.index(), loser.index());
+
+        ensure!(w < len && l < len, "unknown species in rule {w} > {l}");
+        ensure!(w != l, "{} can not beat itself", self.species[w].name);
+        ensure!(
+            self.outcomes[w * len + l] != FightResults::Lost,
+            "{} already beats {}",
+            self.species[l].name,
+            self.species[w].name
+        );
+
+        self.outcomes[w * len + l] = FightResults::Won;
+        self.outcomes[l * len + w] = FightResults::Lost;
+        Ok(())
+    }
+
+    pub fn fight(&self, a: Species, b: Species) -> FightResults {
+        self.outcomes[a.index() * self.len() + b.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.species.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.species.is_empty()
+    }
+
+    pub fn info(&self, species: Species) -> &SpeciesInfo {
+        &self.species[species.index()]
+    }
+
+    pub fn name(&self, species: Species) -> &str {
+        &self.info(species).name
+    }
+
+    pub fn find(&self, name: &str) -> Option<Species> {
+        self.species
+            .iter()
+            .position(|info| info.name == name)
+            .map(|i| Species(i as u8))
+    }
+
+    pub fn species(&self) -> impl Iterator<Item = Species> {
+        (0..self.len()).map(|i| Species(i as u8))
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classic_matches_shape_fight() {
+        let ruleset = Ruleset::classic();
+        let shapes = [Shape::Rock, Shape::Paper, Shape::Scissors];
+
+        for a in shapes {
+            for b in shapes {
+                assert_eq!(ruleset.fight(a.into(), b.into()), a.fight(b));
+            }
+        }
+    }
+
+    #[test]
+    fn balanced_rulesets_are_balanced() {
+        for ruleset in [Ruleset::rpsls(), Ruleset::balanced(7).unwrap()] {
+            for a in ruleset.species() {
+                let wins = ruleset
+                    .species()
+                    .filter(|&b| ruleset.fight(a, b) == FightResults::Won)
+                    .count();
+                let losses = ruleset
+                    .species()
+                    .filter(|&b| ruleset.fight(a, b) == FightResults::Lost)
+                    .count();
+
+                assert_eq!(wins, (ruleset.len() - 1) / 2);
+                assert_eq!(losses, wins);
+            }
+        }
+    }
+
+    #[test]
+    fn rpsls_rules() {
+        use FightResults::*;
+
+        let ruleset = Ruleset::rpsls();
+        let species = |name| ruleset.find(name).unwrap();
+
+        assert_eq!(ruleset.fight(species("spock"), species("rock")), Won);
+        assert_eq!(ruleset.fight(species("lizard"), species("paper")), Won);
+        assert_eq!(ruleset.fight(species("scissors"), species("lizard")), Won);
+        assert_eq!(ruleset.fight(species("rock"), species("paper")), Lost);
+    }
+
+    #[test]
+    fn species_look_different() {
+        for ruleset in [Ruleset::rpsls(), Ruleset::balanced(255).unwrap()] {
+            let mut looks = ruleset
+                .species()
+                .map(|species| {
+                    let info = ruleset.info(species);
+                    (info.atlas_cell, info.tint)
+                })
+                .collect::<Vec<_>>();
+            looks.sort_unstable();
+            looks.dedup();
+
+            assert_eq!(looks.len(), ruleset.len());
+        }
+        assert_eq!(Ruleset::classic().info(Species(2)).tint, [255; 3]);
+    }
+
+    #[test]
+    fn payoff_matrix_and_contradictions() {
+        let species = vec![SpeciesInfo::new("a", [0, 0]), SpeciesInfo::new("b", [1, 0])];
+
+        let ruleset =
+            Ruleset::from_payoff_matrix(species.clone(), &[vec![0.0, 2.0], vec![-1.0, 0.0]])
+                .unwrap();
+        assert_eq!(ruleset.fight(Species(0), Species(1)), FightResults::Won);
+
+        let edges = [(Species(0), Species(1)), (Species(1), Species(0))];
+        assert!(Ruleset::from_tournament(species, &edges).is_err());
+    }
+}
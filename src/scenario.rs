@@ -0,0 +1,1284 @@
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, ensure, Context as _, Result};
+
+use crate::{
+    Boundary, Combat, Deg, Ecology, FightModel, FightResults, FightTiming, Lattice, LatticeBuilder,
+    LatticeRates, LatticeSchedule, Mutation, MutationMatrix, Obstacle, Rad, Ruleset, SpawnMask,
+    SpawnPattern, Species, SpeciesInfo, Stalemate, Steering, TieBreak, WinMatrix, World,
+    WorldBuilder,
+};
+
+/// Spawn count and movement parameters of one species of a [`Scenario`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeciesScenario {
+    pub count: usize,
+    pub steering: Steering,
+}
+
+/// Size and moves of the [`Lattice`] a [`Scenario`] runs on instead of a
+/// [`World`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatticeScenario {
+    pub width: usize,
+    pub height: usize,
+    pub rates: LatticeRates,
+    pub schedule: LatticeSchedule,
+}
+
+impl Default for LatticeScenario {
+    fn default() -> Self {
+        let builder = LatticeBuilder::default();
+        Self {
+            width: builder.width,
+            height: builder.height,
+            rates: builder.rates,
+            schedule: builder.schedule,
+        }
+    }
+}
+
+/// Everything needed to set up a [`World`], usually loaded from a text file.
+///
+/// ```text
+/// # top level keys apply to the whole arena
+/// seed = 42
+/// arena = 640 480
+/// boundary = reflect   # wrap, reflect or absorb
+/// jitter = 90
+/// contact_radius = 16
+/// time_limit = 600        # seconds, stops a run that takes too long
+/// stalemate_after = 60    # seconds without any conversion
+/// obstacle = rect 300 100 40 280   # x y width height, repeatable
+/// obstacle = circle 120 240 50     # center x y, radius
+/// line_of_sight = true             # obstacles hide agents from each other
+/// spawn = clusters 40   # uniform, poisson_disk MIN_DISTANCE, clusters SPREAD,
+///                       # rings, stripes or mask IMAGE
+/// fight = per_contact 0.8   # deterministic, or the chance winners convert
+///                           # losers per_contact or per_second
+/// ties = push_apart         # nothing, push_apart or random
+/// damage = 0.34 0 0.1   # hit points: health a hit takes from a species the
+///                       # attacker beats, ties with and loses to
+/// cooldown = 0.5        # seconds between two hits
+/// healing = 0.05        # health regained per second
+/// mutation = 0.001      # chance of every agent mutating on each tick
+/// prey_energy = 1       # ecology: winners eat losers for this much energy,
+/// metabolism = 0.1      # burn this much per second and starve at zero,
+/// reproduction_threshold = 2   # split in two at this much energy
+/// carrying_capacity = 1000     # while the population is below this
+/// initial_energy = 1           # energy of the agents spawned at the start
+/// lattice = 200 150     # cells, runs on a lattice where counts are cells
+/// schedule = async      # async or sync lattice updates
+/// selection = 1         # relative rates of the lattice moves
+/// reproduction = 1
+/// exchange = 1
+///
+/// [species rock]
+/// atlas = 0 0
+/// tint = 255 255 255    # multiplies the sprite, past the third species
+///                       # sprites are reused with a tint by default
+/// count = 30
+/// speed = 60
+/// beats = scissors
+/// converts = scissors 0.9 paper 0.1   # overrides the chances of converting
+///                                     # these, not of being converted
+/// mutates = paper 3 scissors 1   # weights of the species mutants turn into,
+///                                # any other one with equal odds by default
+/// sensing_radius = 80
+/// chase_weight = 1
+/// flee_weight = 1
+/// max_turn_rate = 180   # degrees per second
+/// avoid_weight = 2
+/// mask_color = 255 0 0  # pixels of the spawn mask this species starts on
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub seed: u64,
+    pub width: f32,
+    pub height: f32,
+    pub boundary: Boundary,
+    pub jitter: f32,
+    pub contact_radius: f32,
+    pub stalemate: Stalemate,
+    pub obstacles: Vec<Obstacle>,
+    pub line_of_sight: bool,
+    pub spawn: SpawnPattern,
+    pub fight: FightModel,
+    pub mutation: Mutation,
+    pub ecology: Option<Ecology>,
+    /// Runs the scenario on a lattice instead of a world when set.
+    pub lattice: Option<LatticeScenario>,
+    pub ruleset: Ruleset,
+    /// Indexed by species.
+    pub species: Vec<SpeciesScenario>,
+}
+
+impl Scenario {
+    /// Scenario spawning `count` agents of every species of `ruleset`.
+    pub fn from_ruleset(ruleset: Ruleset, count: usize) -> Self {
+        let species = ruleset
+            .species()
+            .map(|_| SpeciesScenario {
+                count,
+                steering: Steering::default(),
+            })
+            .collect();
+
+        Self {
+            seed: 0,
+            width: 640.0,
+            height: 480.0,
+            boundary: Boundary::default(),
+            jitter: 90.0,
+            contact_radius: World::DEFAULT_CONTACT_RADIUS,
+            stalemate: Stalemate::default(),
+            obstacles: Vec::new(),
+            line_of_sight: false,
+            spawn: SpawnPattern::default(),
+            fight: FightModel::default(),
+            mutation: Mutation::default(),
+            ecology: None,
+            lattice: None,
+            ruleset,
+            species,
+        }
+    }
+
+    pub fn load<P>(filepath: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        let source = std::fs::read_to_string(filepath)
+            .with_context(|| format!("Could not read scenario {}", filepath.display()))?;
+
+        let parser = Parser {
+            directory: filepath.parent(),
+            ..Default::default()
+        };
+        parser
+            .parse(&source)
+            .with_context(|| format!("Invalid scenario {}", filepath.display()))
+    }
+
+    /// Parses the scenario format, errors point at the offending line and
+    /// column. Spawn masks are looked up from the current directory.
+    pub fn parse(source: &str) -> Result<Self> {
+        Parser::default().parse(source)
+    }
+
+    /// Sets a numeric parameter by name, using the keys of the scenario
+    /// format: `jitter`, `contact_radius`, `time_limit`, `stalemate_after`,
+    /// or `species.NAME.KEY` where `KEY` is `count`, `speed`,
+    /// `sensing_radius`, `chase_weight`, `flee_weight`, `max_turn_rate`,
+    /// `avoid_weight` or `ratio`, or `species.NAME.converts.OTHER` for
+    /// the chance of converting another species in a probabilistic fight.
+    /// Hit points combat adds `damage_won`, `damage_tied`, `damage_lost`,
+    /// `cooldown` and `healing`, mutations `mutation` for the rate and
+    /// `species.NAME.mutates.OTHER` for the weight of a mutation. Ecologies
+    /// add `initial_energy`, `prey_energy`, `metabolism`,
+    /// `reproduction_threshold` and `carrying_capacity`, lattices
+    /// `selection`, `reproduction` and `exchange`.
+    ///
+    /// `ratio` gives the species that fraction of the total population, the
+    /// other species share the rest in the same proportions as before.
+    /// Values the scenario format would reject fail the same way.
+    pub fn set_param(&mut self, name: &str, value: f64) -> Result<()> {
+        // checked after the conversion, so values too large for an f32 fail
+        let single = value as f32;
+        let parts = name.split('.').collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["jitter"] => self.jitter = non_negative(name, single)?,
+            ["contact_radius"] => self.contact_radius = positive(name, single)?,
+            ["time_limit"] => self.stalemate.time_limit = Some(non_negative(name, value)?),
+            ["stalemate_after"] => self.stalemate.quiet_period = Some(non_negative(name, value)?),
+            ["mutation"] => self.mutation.rate = chance(name, single)?,
+            [key @ ("selection" | "reproduction" | "exchange")] => {
+                let Some(lattice) = &mut self.lattice else {
+                    bail!("{name:?} needs a lattice");
+                };
+                let rate = match *key {
+                    "selection" => &mut lattice.rates.selection,
+                    "reproduction" => &mut lattice.rates.reproduction,
+                    _ => &mut lattice.rates.exchange,
+                };
+                *rate = non_negative(name, single)?;
+            }
+            [key @ ("initial_energy"
+            | "prey_energy"
+            | "metabolism"
+            | "reproduction_threshold"
+            | "carrying_capacity")] => {
+                let Some(ecology) = &mut self.ecology else {
+                    bail!("{name:?} needs an ecology");
+                };
+                match *key {
+                    "initial_energy" => ecology.initial_energy = positive(name, single)?,
+                    "prey_energy" => ecology.prey_energy = non_negative(name, single)?,
+                    "metabolism" => ecology.metabolism = non_negative(name, single)?,
+                    "reproduction_threshold" => {
+                        ecology.reproduction_threshold = positive(name, single)?;
+                    }
+                    _ => ecology.carrying_capacity = non_negative(name, value)? as usize,
+                }
+            }
+            [key @ ("damage_won" | "damage_tied" | "damage_lost" | "cooldown" | "healing")] => {
+                let Some(combat) = &mut self.fight.combat else {
+                    bail!("{name:?} needs hit points combat");
+                };
+                let field = match *key {
+                    "damage_won" => &mut combat.damage_won,
+                    "damage_tied" => &mut combat.damage_tied,
+                    "damage_lost" => &mut combat.damage_lost,
+                    "cooldown" => &mut combat.cooldown,
+                    _ => &mut combat.healing,
+                };
+                *field = non_negative(name, single)?;
+            }
+            ["species", a, "converts", b] => {
+                let find = |name: &str| {
+                    self.ruleset
+                        .find(name)
+                        .ok_or_else(|| anyhow!("unknown species {name:?}"))
+                };
+                let (a, b) = (find(a)?, find(b)?);
+                let Some(odds) = &mut self.fight.odds else {
+                    bail!("{name:?} needs a probabilistic fight model");
+                };
+                odds.set(a, b, value as f32)?;
+            }
+            ["species", a, "mutates", b] => {
+                let find = |name: &str| {
+                    self.ruleset
+                        .find(name)
+                        .ok_or_else(|| anyhow!("unknown species {name:?}"))
+                };
+                let (a, b) = (find(a)?, find(b)?);
+                let len = self.ruleset.len();
+                self.mutation
+                    .matrix
+                    .get_or_insert_with(|| MutationMatrix::uniform(len))
+                    .set(a, b, value as f32)?;
+            }
+            ["species", species, key] => {
+                let species = self
+                    .ruleset
+                    .find(species)
+                    .ok_or_else(|| anyhow!("unknown species {species:?}"))?;
+                let steering = &mut self.species[species.index()].steering;
+
+                match *key {
+                    "count" => {
+                        self.species[species.index()].count = non_negative(name, value)? as usize;
+                    }
+                    "speed" => steering.max_speed = non_negative(name, single)?,
+                    "sensing_radius" => steering.sensing_radius = positive(name, single)?,
+                    "chase_weight" => steering.chase_weight = finite(name, single)?,
+                    "flee_weight" => steering.flee_weight = finite(name, single)?,
+                    "max_turn_rate" => {
+                        steering.max_turn_rate = Rad::from(Deg(non_negative(name, single)?)).0;
+                    }
+                    "avoid_weight" => steering.avoid_weight = finite(name, single)?,
+                    "ratio" => self.set_ratio(species, value)?,
+                    _ => bail!("unknown parameter {name:?}"),
+                }
+            }
+            _ => bail!("unknown parameter {name:?}"),
+        }
+
+        Ok(())
+    }
+
+    fn set_ratio(&mut self, species: Species, ratio: f64) -> Result<()> {
+        ensure!(
+            (0.0..=1.0).contains(&ratio),
+            "ratios must be between 0 and 1, not {ratio}"
+        );
+
+        let total = self.species.iter().map(|s| s.count).sum::<usize>();
+        let count = (total as f64 * ratio).round() as usize;
+        let rest = total - count;
+
+        // the other species keep their proportions, or share evenly when
+        // they are all empty
+        let others = total - self.species[species.index()].count;
+        let weights = self
+            .species
+            .iter()
+            .enumerate()
+            .map(|(i, config)| match (i == species.index(), others) {
+                (true, _) => 0,
+                (false, 0) => 1,
+                (false, _) => config.count,
+            })
+            .collect::<Vec<_>>();
+        let weight_total = weights.iter().sum::<usize>().max(1);
+
+        // round down, then give the agents lost to rounding to the first species
+        let mut remaining = rest;
+        for (config, &weight) in self.species.iter_mut().zip(&weights) {
+            config.count = rest * weight / weight_total;
+            remaining -= config.count;
+        }
+        self.species[species.index()].count = count;
+        for (config, &weight) in self.species.iter_mut().zip(&weights) {
+            if remaining > 0 && weight > 0 {
+                config.count += 1;
+                remaining -= 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn world_builder(&self) -> WorldBuilder {
+        let mut builder = WorldBuilder::new()
+            .size(self.width, self.height)
+            .boundary(self.boundary)
+            .seed(self.seed)
+            .jitter(self.jitter)
+            .contact_radius(self.contact_radius)
+            .stalemate(self.stalemate)
+            .line_of_sight(self.line_of_sight)
+            .spawn(self.spawn.clone())
+            .ruleset(self.ruleset.clone())
+            .fight(self.fight.clone())
+            .mutation(self.mutation.clone());
+        if let Some(ecology) = self.ecology {
+            builder = builder.ecology(ecology);
+        }
+        for &obstacle in &self.obstacles {
+            builder = builder.obstacle(obstacle);
+        }
+
+        for (species, config) in self.ruleset.species().zip(&self.species) {
+            builder = builder
+                .steering(species, config.steering)
+                .population(species, config.count);
+        }
+
+        builder
+    }
+
+    pub fn build_world(&self) -> Result<World> {
+        self.world_builder().build()
+    }
+
+    /// Builder of the lattice the scenario runs on, if it does.
+    pub fn lattice_builder(&self) -> Option<LatticeBuilder> {
+        let lattice = self.lattice?;
+        let mut builder = LatticeBuilder::new()
+            .size(lattice.width, lattice.height)
+            .seed(self.seed)
+            .rates(lattice.rates)
+            .schedule(lattice.schedule)
+            .ruleset(self.ruleset.clone());
+        for (species, config) in self.ruleset.species().zip(&self.species) {
+            builder = builder.population(species, config.count);
+        }
+
+        Some(builder)
+    }
+
+    /// `None` unless the scenario runs on a lattice.
+    pub fn build_lattice(&self) -> Result<Option<Lattice>> {
+        self.lattice_builder()
+            .map(LatticeBuilder::build)
+            .transpose()
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self::from_ruleset(Ruleset::classic(), 30)
+    }
+}
+
+/// Position of a token in the source, both 1-based.
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    line: usize,
+    column: usize,
+}
+
+impl Location {
+    fn error<M>(self, message: M) -> anyhow::Error
+    where
+        M: Display,
+    {
+        anyhow!("line {}, column {}: {message}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token<'a> {
+    text: &'a str,
+    location: Location,
+}
+
+impl Token<'_> {
+    fn parse<T>(&self) -> Result<T>
+    where
+        T: FromStr,
+    {
+        self.text.parse().map_err(|_| {
+            self.location
+                .error(format!("invalid value {:?}", self.text))
+        })
+    }
+
+    /// Parses a number and runs it through one of the range checks below,
+    /// `key` names it in the error.
+    fn checked<T>(&self, key: &str, check: fn(&str, T) -> Result<T>) -> Result<T>
+    where
+        T: FromStr,
+    {
+        check(key, self.parse()?).map_err(|e| self.location.error(e))
+    }
+}
+
+/// `value` if it is finite and above zero.
+fn positive<T>(key: &str, value: T) -> Result<T>
+where
+    T: Into<f64> + Copy + Display,
+{
+    let v = value.into();
+    ensure!(
+        v.is_finite() && v > 0.0,
+        "{key} must be positive, not {value}"
+    );
+    Ok(value)
+}
+
+/// `value` if it is finite and zero or more.
+fn non_negative<T>(key: &str, value: T) -> Result<T>
+where
+    T: Into<f64> + Copy + Display,
+{
+    let v = value.into();
+    ensure!(
+        v.is_finite() && v >= 0.0,
+        "{key} must be zero or more, not {value}"
+    );
+    Ok(value)
+}
+
+/// `value` if it is a chance, between 0 and 1.
+fn chance<T>(key: &str, value: T) -> Result<T>
+where
+    T: Into<f64> + Copy + Display,
+{
+    ensure!(
+        (0.0..=1.0).contains(&value.into()),
+        "{key} must be between 0 and 1, not {value}"
+    );
+    Ok(value)
+}
+
+/// `value` if it is finite.
+fn finite<T>(key: &str, value: T) -> Result<T>
+where
+    T: Into<f64> + Copy + Display,
+{
+    ensure!(
+        value.into().is_finite(),
+        "{key} must be finite, not {value}"
+    );
+    Ok(value)
+}
+
+#[derive(Debug)]
+struct SpeciesSection<'a> {
+    info: SpeciesInfo,
+    config: SpeciesScenario,
+    beats: Vec<Token<'a>>,
+    /// Species and chance of converting it.
+    converts: Vec<(Token<'a>, Token<'a>)>,
+    /// Species and weight of mutating into it.
+    mutates: Vec<(Token<'a>, Token<'a>)>,
+    mask_color: Option<[u8; 3]>,
+}
+
+#[derive(Debug, Default)]
+struct Parser<'a> {
+    /// Directory spawn masks are relative to.
+    directory: Option<&'a Path>,
+    seed: u64,
+    arena: Option<(f32, f32)>,
+    boundary: Boundary,
+    jitter: Option<f32>,
+    contact_radius: Option<f32>,
+    stalemate: Stalemate,
+    obstacles: Vec<Obstacle>,
+    line_of_sight: bool,
+    spawn: SpawnPattern,
+    /// `None` for deterministic fights, the timing and chance of winning
+    /// otherwise, along with where the chance was given.
+    fight: Option<(FightTiming, f32, Location)>,
+    ties: TieBreak,
+    /// Set by any of the hit points keys.
+    combat: Option<Combat>,
+    mutation: f32,
+    /// Set by any of the ecology keys.
+    ecology: Option<Ecology>,
+    /// Set by any of the lattice keys.
+    lattice: Option<LatticeScenario>,
+    species: Vec<SpeciesSection<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(mut self, source: &'a str) -> Result<Scenario> {
+        for (i, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let tokens = tokenize(line, i + 1);
+
+            let Some(first) = tokens.first() else {
+                continue;
+            };
+
+            if first.text.starts_with('[') {
+                self.section(line, &tokens)?;
+            } else {
+                self.key_value(&tokens)?;
+            }
+        }
+
+        self.finish()
+    }
+
+    fn section(&mut self, line: &str, tokens: &[Token<'a>]) -> Result<()> {
+        let location = tokens[0].location;
+        let header = line
+            .trim()
+            .strip_prefix('[')
+            .and_then(|header| header.strip_suffix(']'))
+            .ok_or_else(|| location.error("unterminated section header"))?;
+
+        match header.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["species", name] => {
+                if self.species.iter().any(|s| s.info.name == *name) {
+                    return Err(tokens[1]
+                        .location
+                        .error(format!("duplicate species {name:?}")));
+                }
+
+                self.species.push(SpeciesSection {
+                    info: SpeciesInfo::nth(self.species.len(), *name),
+                    config: SpeciesScenario {
+                        count: 0,
+                        steering: Steering::default(),
+                    },
+                    beats: Vec::new(),
+                    converts: Vec::new(),
+                    mutates: Vec::new(),
+                    mask_color: None,
+                });
+                Ok(())
+            }
+            _ => Err(location.error(format!("unknown section [{header}]"))),
+        }
+    }
+
+    fn key_value(&mut self, tokens: &[Token<'a>]) -> Result<()> {
+        let key = &tokens[0];
+        match tokens.get(1) {
+            Some(token) if token.text == "=" => {}
+            Some(token) => return Err(token.location.error("expected '='")),
+            None => return Err(key.location.error("expected '=' after the key")),
+        }
+
+        let values = &tokens[2..];
+        let value = |n: usize| -> Result<&Token<'a>> {
+            match values.get(n) {
+                Some(token) => Ok(token),
+                None => Err(tokens.last().unwrap().location.error(format!(
+                    "{} expects {} value{}",
+                    key.text,
+                    n + 1,
+                    if n == 0 { "" } else { "s" }
+                ))),
+            }
+        };
+
+        let Some(section) = self.species.last_mut() else {
+            match key.text {
+                "seed" => self.seed = value(0)?.parse()?,
+                "arena" => {
+                    let width = value(0)?.checked("the arena width", positive)?;
+                    let height = value(1)?.checked("the arena height", positive)?;
+                    self.arena = Some((width, height));
+                }
+                "boundary" => self.boundary = value(0)?.parse()?,
+                "jitter" => self.jitter = Some(value(0)?.checked("jitter", non_negative)?),
+                "contact_radius" => {
+                    self.contact_radius = Some(value(0)?.checked("contact_radius", positive)?);
+                }
+                "time_limit" => {
+                    self.stalemate.time_limit =
+                        Some(value(0)?.checked("time_limit", non_negative)?);
+                }
+                "stalemate_after" => {
+                    self.stalemate.quiet_period =
+                        Some(value(0)?.checked("stalemate_after", non_negative)?);
+                }
+                "obstacle" => {
+                    let obstacle = match value(0)?.text {
+                        "rect" => Obstacle::rect(
+                            value(1)?.checked("the obstacle position", finite)?,
+                            value(2)?.checked("the obstacle position", finite)?,
+                            value(3)?.checked("the obstacle width", positive)?,
+                            value(4)?.checked("the obstacle height", positive)?,
+                        ),
+                        "circle" => Obstacle::circle(
+                            value(1)?.checked("the obstacle position", finite)?,
+                            value(2)?.checked("the obstacle position", finite)?,
+                            value(3)?.checked("the obstacle radius", positive)?,
+                        ),
+                        shape => {
+                            return Err(values[0].location.error(format!(
+                                "unknown obstacle {shape:?}, expected rect or circle"
+                            )))
+                        }
+                    };
+                    self.obstacles.push(obstacle);
+                }
+                "line_of_sight" => self.line_of_sight = value(0)?.parse()?,
+                "fight" => {
+                    let timing = match value(0)?.text {
+                        "deterministic" => None,
+                        "per_contact" => Some(FightTiming::PerContact),
+                        "per_second" => Some(FightTiming::PerSecond),
+                        model => {
+                            return Err(values[0]
+                                .location
+                                .error(format!("unknown fight model {model:?}")))
+                        }
+                    };
+                    let (p, location) = match values.get(1) {
+                        Some(token) => (token.checked("the fight chance", chance)?, token.location),
+                        None => (1.0, values[0].location),
+                    };
+                    self.fight = timing.map(|timing| (timing, p, location));
+                }
+                "ties" => self.ties = value(0)?.parse()?,
+                "mutation" => self.mutation = value(0)?.checked("mutation", chance)?,
+                "lattice" | "schedule" | "selection" | "reproduction" | "exchange" => {
+                    let lattice = self.lattice.get_or_insert_with(LatticeScenario::default);
+                    match key.text {
+                        "lattice" => {
+                            let width = value(0)?.checked::<u32>("the lattice width", positive)?;
+                            let height =
+                                value(1)?.checked::<u32>("the lattice height", positive)?;
+                            (lattice.width, lattice.height) = (width as usize, height as usize);
+                        }
+                        "schedule" => lattice.schedule = value(0)?.parse()?,
+                        "selection" => {
+                            lattice.rates.selection = value(0)?.checked(key.text, non_negative)?;
+                        }
+                        "reproduction" => {
+                            lattice.rates.reproduction =
+                                value(0)?.checked(key.text, non_negative)?;
+                        }
+                        _ => lattice.rates.exchange = value(0)?.checked(key.text, non_negative)?,
+                    }
+                }
+                "initial_energy"
+                | "prey_energy"
+                | "metabolism"
+                | "reproduction_threshold"
+                | "carrying_capacity" => {
+                    let ecology = self.ecology.get_or_insert_with(Ecology::default);
+                    match key.text {
+                        "initial_energy" => {
+                            ecology.initial_energy = value(0)?.checked(key.text, positive)?;
+                        }
+                        "prey_energy" => {
+                            ecology.prey_energy = value(0)?.checked(key.text, non_negative)?;
+                        }
+                        "metabolism" => {
+                            ecology.metabolism = value(0)?.checked(key.text, non_negative)?;
+                        }
+                        "reproduction_threshold" => {
+                            ecology.reproduction_threshold =
+                                value(0)?.checked(key.text, positive)?;
+                        }
+                        _ => ecology.carrying_capacity = value(0)?.parse()?,
+                    }
+                }
+                "damage" => {
+                    let combat = self.combat.get_or_insert_with(Combat::default);
+                    combat.damage_won = value(0)?.checked("damage", non_negative)?;
+                    combat.damage_tied = value(1)?.checked("damage", non_negative)?;
+                    combat.damage_lost = value(2)?.checked("damage", non_negative)?;
+                }
+                "cooldown" => {
+                    let cooldown = value(0)?.checked("cooldown", non_negative)?;
+                    self.combat.get_or_insert_with(Combat::default).cooldown = cooldown;
+                }
+                "healing" => {
+                    let healing = value(0)?.checked("healing", non_negative)?;
+                    self.combat.get_or_insert_with(Combat::default).healing = healing;
+                }
+                "spawn" => {
+                    self.spawn = match value(0)?.text {
+                        "uniform" => SpawnPattern::Uniform,
+                        "poisson_disk" => SpawnPattern::PoissonDisk {
+                            min_distance: value(1)?
+                                .checked("the minimum distance", non_negative)?,
+                        },
+                        "clusters" => SpawnPattern::Clusters {
+                            spread: value(1)?.checked("the spread", non_negative)?,
+                        },
+                        "rings" => SpawnPattern::Rings,
+                        "stripes" => SpawnPattern::Stripes,
+                        "mask" => {
+                            let token = value(1)?;
+                            let filepath = self.directory.unwrap_or(Path::new("")).join(token.text);
+                            let mask = SpawnMask::load(filepath, Vec::new())
+                                .map_err(|e| token.location.error(format!("{e:#}")))?;
+                            SpawnPattern::Mask(mask)
+                        }
+                        pattern => {
+                            return Err(values[0]
+                                .location
+                                .error(format!("unknown spawn pattern {pattern:?}")))
+                        }
+                    };
+                }
+                _ => return Err(key.location.error(format!("unknown key {:?}", key.text))),
+            }
+            return Ok(());
+        };
+
+        let steering = &mut section.config.steering;
+        match key.text {
+            "atlas" => section.info.atlas_cell = [value(0)?.parse()?, value(1)?.parse()?],
+            "tint" => {
+                section.info.tint = [value(0)?.parse()?, value(1)?.parse()?, value(2)?.parse()?];
+            }
+            "count" => section.config.count = value(0)?.parse()?,
+            "speed" => steering.max_speed = value(0)?.checked("speed", non_negative)?,
+            "sensing_radius" => {
+                steering.sensing_radius = value(0)?.checked("sensing_radius", positive)?;
+            }
+            "chase_weight" => steering.chase_weight = value(0)?.checked("chase_weight", finite)?,
+            "flee_weight" => steering.flee_weight = value(0)?.checked("flee_weight", finite)?,
+            "avoid_weight" => steering.avoid_weight = value(0)?.checked("avoid_weight", finite)?,
+            "mask_color" => {
+                section.mask_color =
+                    Some([value(0)?.parse()?, value(1)?.parse()?, value(2)?.parse()?]);
+            }
+            "max_turn_rate" => {
+                let degrees = value(0)?.checked("max_turn_rate", non_negative)?;
+                steering.max_turn_rate = Rad::from(Deg(degrees)).0;
+            }
+            "beats" => {
+                value(0)?;
+                section.beats.extend_from_slice(values);
+            }
+            "converts" | "mutates" => {
+                value(1)?;
+                if values.len() % 2 == 1 {
+                    return Err(values[values.len() - 1].location.error(format!(
+                        "expected a {} after the species",
+                        if key.text == "converts" {
+                            "chance"
+                        } else {
+                            "weight"
+                        }
+                    )));
+                }
+                let pairs = match key.text {
+                    "converts" => &mut section.converts,
+                    _ => &mut section.mutates,
+                };
+                for pair in values.chunks(2) {
+                    pairs.push((pair[0].clone(), pair[1].clone()));
+                }
+            }
+            _ => return Err(key.location.error(format!("unknown key {:?}", key.text))),
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Scenario> {
+        if self.species.is_empty() {
+            bail!("the scenario does not define any [species NAME] section");
+        }
+
+        if let SpawnPattern::Mask(mask) = &mut self.spawn {
+            mask.colors = self
+                .species
+                .iter()
+                .enumerate()
+                .filter_map(|(i, s)| Some((s.mask_color?, Species(i as u8))))
+                .collect();
+        }
+
+        let infos = self.species.iter().map(|s| s.info.clone()).collect();
+        let mut ruleset = Ruleset::new(infos)?;
+
+        for (winner, section) in self.species.iter().enumerate() {
+            for token in &section.beats {
+                let loser = ruleset.find(token.text).ok_or_else(|| {
+                    token
+                        .location
+                        .error(format!("unknown species {:?}", token.text))
+                })?;
+
+                ruleset
+                    .set_beats(Species(winner as u8), loser)
+                    .map_err(|e| token.location.error(e))?;
+            }
+        }
+
+        let fight = self.fight_model(&ruleset)?;
+        let mutation = self.mutation(&ruleset)?;
+
+        let defaults = Scenario::from_ruleset(Ruleset::classic(), 0);
+        let (width, height) = self.arena.unwrap_or((defaults.width, defaults.height));
+
+        Ok(Scenario {
+            seed: self.seed,
+            width,
+            height,
+            boundary: self.boundary,
+            jitter: self.jitter.unwrap_or(defaults.jitter),
+            contact_radius: self.contact_radius.unwrap_or(defaults.contact_radius),
+            stalemate: self.stalemate,
+            obstacles: self.obstacles,
+            line_of_sight: self.line_of_sight,
+            spawn: self.spawn,
+            fight,
+            mutation,
+            ecology: self.ecology,
+            lattice: self.lattice,
+            ruleset,
+            species: self.species.into_iter().map(|s| s.config).collect(),
+        })
+    }
+
+    /// Winners of the ruleset convert losers with the chance given to
+    /// `fight`, unless `converts` sets the chance of that species converting
+    /// the other. Overrides only apply in the direction they are written.
+    fn fight_model(&self, ruleset: &Ruleset) -> Result<FightModel> {
+        let Some((timing, p, location)) = self.fight else {
+            if let Some((token, _)) = self.species.iter().flat_map(|s| &s.converts).next() {
+                return Err(token
+                    .location
+                    .error("converts needs a per_contact or per_second fight"));
+            }
+            return Ok(FightModel {
+                combat: self.combat,
+                ..FightModel::deterministic().ties(self.ties)
+            });
+        };
+
+        let mut overrides = Vec::new();
+        for (a, section) in self.species.iter().enumerate() {
+            for (name, chance) in &section.converts {
+                let b = ruleset.find(name.text).ok_or_else(|| {
+                    name.location
+                        .error(format!("unknown species {:?}", name.text))
+                })?;
+                overrides.push((Species(a as u8), b, chance));
+            }
+        }
+
+        let mut odds = WinMatrix::new(ruleset.len(), timing);
+        for a in ruleset.species() {
+            for b in ruleset.species() {
+                let overridden = overrides.iter().any(|&(x, y, _)| (x, y) == (a, b));
+                if !overridden && ruleset.fight(a, b) == FightResults::Won {
+                    odds.set(a, b, p).map_err(|e| location.error(e))?;
+                }
+            }
+        }
+        for (a, b, chance) in overrides {
+            odds.set(a, b, chance.parse()?)
+                .map_err(|e| chance.location.error(e))?;
+        }
+
+        Ok(FightModel {
+            combat: self.combat,
+            ..FightModel::probabilistic(odds).ties(self.ties)
+        })
+    }
+
+    /// Mutants of species with `mutates` entries only turn into the listed
+    /// species, the others into any other species with equal odds.
+    fn mutation(&self, ruleset: &Ruleset) -> Result<Mutation> {
+        let mutation = Mutation::new(self.mutation);
+        if self.species.iter().all(|s| s.mutates.is_empty()) {
+            return Ok(mutation);
+        }
+
+        let mut matrix = MutationMatrix::uniform(ruleset.len());
+        for (a, section) in self.species.iter().enumerate() {
+            let a = Species(a as u8);
+            if !section.mutates.is_empty() {
+                for b in ruleset.species().filter(|&b| b != a) {
+                    matrix.set(a, b, 0.0)?;
+                }
+            }
+
+            for (name, weight) in &section.mutates {
+                let b = ruleset.find(name.text).ok_or_else(|| {
+                    name.location
+                        .error(format!("unknown species {:?}", name.text))
+                })?;
+                matrix
+                    .set(a, b, weight.parse()?)
+                    .map_err(|e| name.location.error(e))?;
+            }
+        }
+
+        Ok(mutation.matrix(matrix))
+    }
+}
+
+/// Splits a line on whitespace, keeping `=` as a token of its own.
+fn tokenize(line: &str, line_number: usize) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    let mut push = |from: usize, to: usize| {
+        tokens.push(Token {
+            text: &line[from..to],
+            location: Location {
+                line: line_number,
+                column: line[..from].chars().count() + 1,
+            },
+        });
+    };
+
+    for (i, c) in line.char_indices() {
+        if c.is_whitespace() || c == '=' {
+            if let Some(from) = start.take() {
+                push(from, i);
+            }
+            if c == '=' {
+                push(i, i + 1);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(from) = start {
+        push(from, line.len());
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FightResults;
+
+    const RPS: &str = "\
+# classic rock paper scissors
+seed = 7
+arena = 800 600
+boundary = wrap
+stalemate_after = 30
+obstacle = rect 300 100 40 280
+obstacle = circle 120 240 50
+line_of_sight = true
+
+[species rock]
+atlas = 0 0
+count = 10
+speed = 40
+beats = scissors
+
+[species paper]
+count = 20
+beats = rock
+
+[species scissors]
+count = 30
+max_turn_rate = 90
+beats = paper
+";
+
+    #[test]
+    fn parses_a_scenario() {
+        let scenario = Scenario::parse(RPS).unwrap();
+
+        assert_eq!(scenario.seed, 7);
+        assert_eq!((scenario.width, scenario.height), (800.0, 600.0));
+        assert_eq!(scenario.boundary, Boundary::Wrap);
+        assert_eq!(scenario.stalemate.quiet_period, Some(30.0));
+        assert_eq!(
+            scenario.obstacles,
+            vec![
+                Obstacle::rect(300.0, 100.0, 40.0, 280.0),
+                Obstacle::circle(120.0, 240.0, 50.0)
+            ]
+        );
+        assert!(scenario.line_of_sight);
+        assert_eq!(scenario.ruleset, Ruleset::classic());
+        assert_eq!(scenario.species[1].count, 20);
+        assert_eq!(scenario.species[0].steering.max_speed, 40.0);
+        assert!(
+            (scenario.species[2].steering.max_turn_rate - std::f32::consts::FRAC_PI_2).abs() < 1e-6
+        );
+
+        let world = scenario.build_world().unwrap();
+        assert_eq!(world.populations(), vec![10, 20, 30]);
+        assert_eq!(world.obstacles().len(), 2);
+        assert_eq!(
+            world.ruleset().fight(Species(0), Species(2)),
+            FightResults::Won
+        );
+    }
+
+    #[test]
+    fn parameters_can_be_set_by_name() {
+        let mut scenario = Scenario::parse(RPS).unwrap();
+
+        scenario.set_param("species.scissors.speed", 90.0).unwrap();
+        scenario.set_param("contact_radius", 8.0).unwrap();
+        assert_eq!(scenario.species[2].steering.max_speed, 90.0);
+        assert_eq!(scenario.contact_radius, 8.0);
+        assert!(scenario.set_param("contact_radius", 0.0).is_err());
+        assert!(scenario.set_param("species.rock.speed", -1.0).is_err());
+        assert!(scenario
+            .set_param("species.rock.sensing_radius", 1e40)
+            .is_err());
+        assert!(scenario.set_param("mutation", 1.5).is_err());
+        assert!(scenario.set_param("stalemate_after", f64::NAN).is_err());
+
+        scenario.set_param("species.paper.ratio", 0.5).unwrap();
+        let counts = scenario.species.iter().map(|s| s.count).collect::<Vec<_>>();
+        assert_eq!(counts, vec![8, 30, 22]);
+
+        assert!(scenario.set_param("species.lizard.speed", 1.0).is_err());
+        assert!(scenario.set_param("species.rock.colour", 1.0).is_err());
+    }
+
+    #[test]
+    fn parses_spawn_patterns() {
+        let scenario =
+            Scenario::parse("spawn = poisson_disk 12\n[species a]\ncount = 5\n").unwrap();
+        assert_eq!(
+            scenario.spawn,
+            SpawnPattern::PoissonDisk { min_distance: 12.0 }
+        );
+
+        let directory = std::env::temp_dir().join("rps_simulator_spawn_mask");
+        std::fs::create_dir_all(&directory).unwrap();
+        image::RgbaImage::from_pixel(2, 2, image::Rgba([0, 255, 0, 255]))
+            .save(directory.join("mask.png"))
+            .unwrap();
+        let filepath = directory.join("masked.rps");
+        std::fs::write(
+            &filepath,
+            "spawn = mask mask.png\n[species a]\n[species b]\nmask_color = 0 255 0\n",
+        )
+        .unwrap();
+
+        let scenario = Scenario::load(&filepath).unwrap();
+        let SpawnPattern::Mask(mask) = &scenario.spawn else {
+            panic!("expected a mask, got {:?}", scenario.spawn);
+        };
+        assert_eq!(mask.image.dimensions(), (2, 2));
+        assert_eq!(mask.colors, vec![([0, 255, 0], Species(1))]);
+    }
+
+    #[test]
+    fn parses_fight_models() {
+        let source = "\
+fight = per_second 0.5
+ties = push_apart
+[species rock]
+beats = scissors
+converts = paper 0.2
+[species paper]
+beats = rock
+[species scissors]
+beats = paper
+";
+        let scenario = Scenario::parse(source).unwrap();
+        let odds = scenario.fight.odds.as_ref().unwrap();
+        let (rock, paper, scissors) = (Species(0), Species(1), Species(2));
+
+        assert_eq!(odds.timing, FightTiming::PerSecond);
+        assert_eq!(odds.get(rock, scissors), 0.5);
+        assert_eq!(odds.get(rock, paper), 0.2);
+        // the other way round keeps the chance of the fight
+        assert_eq!(odds.get(paper, rock), 0.5);
+        assert_eq!(scenario.fight.ties, TieBreak::PushApart);
+
+        let mut scenario = scenario;
+        scenario
+            .set_param("species.paper.converts.rock", 0.7)
+            .unwrap();
+        assert!(scenario
+            .set_param("species.paper.converts.rock", 0.9)
+            .is_err());
+        assert!(Scenario::default()
+            .set_param("species.paper.converts.rock", 0.5)
+            .is_err());
+    }
+
+    #[test]
+    fn parses_hit_points_combat() {
+        let mut scenario =
+            Scenario::parse("damage = 0.5 0 0.25\nhealing = 0.1\n[species a]\n").unwrap();
+
+        assert_eq!(
+            scenario.fight.combat,
+            Some(Combat {
+                damage_won: 0.5,
+                damage_tied: 0.0,
+                damage_lost: 0.25,
+                healing: 0.1,
+                ..Combat::default()
+            })
+        );
+
+        scenario.set_param("cooldown", 2.0).unwrap();
+        assert_eq!(scenario.fight.combat.unwrap().cooldown, 2.0);
+        assert!(Scenario::default().set_param("healing", 1.0).is_err());
+    }
+
+    #[test]
+    fn parses_mutations() {
+        let source = "mutation = 0.01\n[species a]\nmutates = c 2\n[species b]\n[species c]\n";
+        let mut scenario = Scenario::parse(source).unwrap();
+        let (a, b, c) = (Species(0), Species(1), Species(2));
+
+        assert_eq!(scenario.mutation.rate, 0.01);
+        let matrix = scenario.mutation.matrix.as_ref().unwrap();
+        assert_eq!((matrix.get(a, b), matrix.get(a, c)), (0.0, 2.0));
+        assert_eq!((matrix.get(b, a), matrix.get(b, c)), (1.0, 1.0));
+
+        scenario.set_param("species.b.mutates.a", 0.0).unwrap();
+        assert_eq!(scenario.mutation.matrix.unwrap().get(b, a), 0.0);
+        assert!(Scenario::parse("[species a]\nmutates = a 1\n").is_err());
+    }
+
+    #[test]
+    fn parses_ecologies() {
+        let source = "metabolism = 0.2\ncarrying_capacity = 200\n[species a]\n";
+        let mut scenario = Scenario::parse(source).unwrap();
+
+        assert_eq!(
+            scenario.ecology,
+            Some(Ecology {
+                metabolism: 0.2,
+                carrying_capacity: 200,
+                ..Ecology::default()
+            })
+        );
+
+        scenario.set_param("prey_energy", 3.0).unwrap();
+        assert_eq!(scenario.ecology.unwrap().prey_energy, 3.0);
+        assert!(Scenario::default().set_param("metabolism", 1.0).is_err());
+    }
+
+    #[test]
+    fn parses_lattices() {
+        let source = "lattice = 40 30\nschedule = sync\nexchange = 2\n[species a]\ncount = 100\n";
+        let mut scenario = Scenario::parse(source).unwrap();
+
+        let expected = LatticeScenario {
+            width: 40,
+            height: 30,
+            rates: LatticeRates {
+                exchange: 2.0,
+                ..LatticeRates::default()
+            },
+            schedule: LatticeSchedule::Synchronous,
+        };
+        assert_eq!(scenario.lattice, Some(expected));
+
+        scenario.set_param("selection", 0.5).unwrap();
+        let lattice = scenario.build_lattice().unwrap().unwrap();
+        assert_eq!(lattice.rates().selection, 0.5);
+        assert_eq!(lattice.populations(), [100]);
+        assert!(Scenario::default().build_lattice().unwrap().is_none());
+        assert!(Scenario::default().set_param("exchange", 1.0).is_err());
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        let error = |source: &str| format!("{:#}", Scenario::parse(source).unwrap_err());
+
+        assert_eq!(
+            error("[species rock]\nbeats = lizard\n"),
+            "line 2, column 9: unknown species \"lizard\""
+        );
+        assert_eq!(
+            error("seed = 7\narena = 800 tall\n"),
+            "line 2, column 13: invalid value \"tall\""
+        );
+        assert_eq!(
+            error("[species a]\nbeats = b\n[species b]\n  beats = a\n"),
+            "line 4, column 11: a already beats b"
+        );
+        assert_eq!(
+            error("obstacle = square 1 2 3\n"),
+            "line 1, column 12: unknown obstacle \"square\", expected rect or circle"
+        );
+        assert_eq!(
+            error("spawn = spiral\n"),
+            "line 1, column 9: unknown spawn pattern \"spiral\""
+        );
+        assert_eq!(
+            error("[species a]\nconverts = a 0.5\n"),
+            "line 2, column 12: converts needs a per_contact or per_second fight"
+        );
+        assert_eq!(
+            error("fight = per_contact 1.5\n[species a]\n[species b]\nbeats = a\n"),
+            "line 1, column 21: the fight chance must be between 0 and 1, not 1.5"
+        );
+        assert_eq!(
+            error("contact_radius = 0\n"),
+            "line 1, column 18: contact_radius must be positive, not 0"
+        );
+        assert_eq!(
+            error("arena = 640 NaN\n"),
+            "line 1, column 13: the arena height must be positive, not NaN"
+        );
+        assert_eq!(
+            error("obstacle = rect 100 100 -20 50\n"),
+            "line 1, column 25: the obstacle width must be positive, not -20"
+        );
+        assert_eq!(
+            error("spawn = clusters -5\n"),
+            "line 1, column 18: the spread must be zero or more, not -5"
+        );
+        assert_eq!(
+            error("[species a]\nsensing_radius = inf\n"),
+            "line 2, column 18: sensing_radius must be positive, not inf"
+        );
+        assert_eq!(
+            error("stalemate_after = -1\n"),
+            "line 1, column 19: stalemate_after must be zero or more, not -1"
+        );
+        assert_eq!(
+            error("mutation = 2\n"),
+            "line 1, column 12: mutation must be between 0 and 1, not 2"
+        );
+        assert_eq!(
+            error("reproduction_threshold = 0\n"),
+            "line 1, column 26: reproduction_threshold must be positive, not 0"
+        );
+        assert_eq!(
+            error("damage = 0.5 NaN 0\n"),
+            "line 1, column 14: damage must be zero or more, not NaN"
+        );
+        assert_eq!(
+            error("lattice = 0 100\n"),
+            "line 1, column 11: the lattice width must be positive, not 0"
+        );
+        assert_eq!(
+            error("colour = red\n"),
+            "line 1, column 1: unknown key \"colour\""
+        );
+    }
+}
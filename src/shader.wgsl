@@ -8,11 +8,13 @@ struct InstanceInput {
     @location(3) model_1: vec3<f32>,
     @location(4) model_3: vec3<f32>,
     @location(5) uv_index: vec2<u32>,
+    @location(6) tint: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
+    @location(1) tint: vec4<f32>,
 }
 
 struct Projection {
@@ -35,6 +37,7 @@ fn vertex(v_in: VertexInput, i_in: InstanceInput) -> VertexOutput {
 
     out.clip_position = projection.ortho * model * vec4<f32>(v_in.position, 0.0, 1.0);
     out.uv = v_in.uv + vec2<f32>(i_in.uv_index);
+    out.tint = i_in.tint;
 
     return out;
 }
@@ -56,5 +59,5 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
 
     let uv = in.uv / grid_size;
 
-    return textureSample(t_view, t_sampler, uv);
+    return textureSample(t_view, t_sampler, uv) * in.tint;
 }
@@ -1,3 +1,5 @@
+use crate::Species;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SimulationState {
     Play,
@@ -5,6 +7,24 @@ pub enum SimulationState {
     Finish,
 }
 
+/// How a simulation ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Finish {
+    /// The only species left, `None` for a stalemate or when no agent is left.
+    pub winner: Option<Species>,
+    /// Simulated time since the start, in seconds.
+    pub elapsed: f64,
+}
+
+/// Conditions ending a simulation that no species manages to win.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Stalemate {
+    /// Simulated seconds after which the simulation ends.
+    pub time_limit: Option<f64>,
+    /// Simulated seconds without any conversion after which the simulation ends.
+    pub quiet_period: Option<f64>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FightResults {
     Won,
@@ -0,0 +1,391 @@
+use crate::{arena::wrap_delta, Vec2};
+
+/// Broad-phase index that buckets points into a uniform grid of cells.
+///
+/// The grid is rebuilt from scratch with a counting sort, which is cheap
+/// enough to do every step and keeps the points of a cell contiguous in
+/// memory. Points outside of the arena are clamped to the border cells,
+/// unless the grid wraps around like a torus.
+#[derive(Debug, Clone)]
+pub struct SpatialGrid {
+    width: f32,
+    height: f32,
+    wrap: bool,
+    columns: usize,
+    rows: usize,
+    cell_width: f32,
+    cell_height: f32,
+    cells: Vec<u32>,
+    cell_start: Vec<u32>,
+    entries: Vec<(u32, Vec2)>,
+}
+
+impl SpatialGrid {
+    /// Cells per side beyond which cells grow instead, so tiny cell sizes
+    /// do not allocate without bounds.
+    pub const MAX_CELLS_PER_SIDE: usize = 1024;
+
+    /// Creates a grid covering a `width` by `height` arena whose cells are
+    /// at least `cell_size` wide and tall.
+    ///
+    /// Panics unless `cell_size` is finite and positive.
+    pub fn new(width: f32, height: f32, cell_size: f32) -> Self {
+        assert!(
+            cell_size.is_finite() && cell_size > 0.0,
+            "the cell size must be finite and positive, not {cell_size}"
+        );
+        let cells =
+            |length: f32| ((length / cell_size) as usize).clamp(1, Self::MAX_CELLS_PER_SIDE);
+        let (columns, rows) = (cells(width), cells(height));
+
+        Self {
+            width,
+            height,
+            wrap: false,
+            columns,
+            rows,
+            cell_width: width / columns as f32,
+            cell_height: height / rows as f32,
+            cells: Vec::new(),
+            cell_start: vec![0; columns * rows + 1],
+            entries: Vec::new(),
+        }
+    }
+
+    /// Creates a grid where the opposite edges of the arena touch, so
+    /// queries near an edge also find the points close to the other side.
+    pub fn wrapping(width: f32, height: f32, cell_size: f32) -> Self {
+        Self {
+            wrap: true,
+            ..Self::new(width, height, cell_size)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Replaces the indexed points, the index of each point is its position
+    /// in the iterator.
+    pub fn rebuild<I>(&mut self, positions: I)
+    where
+        I: IntoIterator<Item = Vec2>,
+    {
+        self.cells.clear();
+        self.entries.clear();
+        for (i, position) in positions.into_iter().enumerate() {
+            self.cells.push(self.cell_of(position) as u32);
+            self.entries.push((i as u32, position));
+        }
+
+        self.cell_start.iter_mut().for_each(|start| *start = 0);
+        for &cell in &self.cells {
+            self.cell_start[cell as usize + 1] += 1;
+        }
+        for cell in 1..self.cell_start.len() {
+            self.cell_start[cell] += self.cell_start[cell - 1];
+        }
+
+        // scatter the points so the ones sharing a cell are stored together
+        let mut cursor = self.cell_start.clone();
+        let mut sorted = vec![(0, Vec2::ZERO); self.entries.len()];
+        for (&cell, &entry) in self.cells.iter().zip(&self.entries) {
+            sorted[cursor[cell as usize] as usize] = entry;
+            cursor[cell as usize] += 1;
+        }
+        self.entries = sorted;
+    }
+
+    /// Calls `f` with the index and position of every point at most `radius`
+    /// away from `position`.
+    ///
+    /// On a wrapping grid the position passed to `f` is the copy of the point
+    /// closest to `position`, which may lie outside of the arena.
+    pub fn for_each_within<F>(&self, position: Vec2, radius: f32, mut f: F)
+    where
+        F: FnMut(usize, Vec2),
+    {
+        if self.wrap {
+            return self.for_each_within_wrapped(position, radius, f);
+        }
+
+        let radius_squared = radius * radius;
+        let (column, row) = self.coords_of(position);
+        let (reach_x, reach_y) = self.reach(radius);
+
+        let (x0, x1) = (
+            column.saturating_sub(reach_x),
+            (column + reach_x).min(self.columns - 1),
+        );
+        for y in row.saturating_sub(reach_y)..=(row + reach_y).min(self.rows - 1) {
+            // the cells of a row are contiguous, so scan them as one slice
+            for &(i, other) in self.cells(y * self.columns + x0, y * self.columns + x1) {
+                if (other - position).length_squared() <= radius_squared {
+                    f(i as usize, other);
+                }
+            }
+        }
+    }
+
+    fn for_each_within_wrapped<F>(&self, position: Vec2, radius: f32, mut f: F)
+    where
+        F: FnMut(usize, Vec2),
+    {
+        let radius_squared = radius * radius;
+        let (column, row) = self.coords_of(position);
+        let (reach_x, reach_y) = self.reach(radius);
+        let column_spans = wrapped_spans(column, reach_x, self.columns);
+
+        for (y0, y1) in wrapped_spans(row, reach_y, self.rows).into_iter().flatten() {
+            for y in y0..=y1 {
+                for &(x0, x1) in column_spans.iter().flatten() {
+                    for &(i, other) in self.cells(y * self.columns + x0, y * self.columns + x1) {
+                        let d = self.wrapped_displacement(position, other);
+                        if d.length_squared() <= radius_squared {
+                            f(i as usize, position + d);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn wrapped_displacement(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let d = to - from;
+        Vec2::new(wrap_delta(d.x, self.width), wrap_delta(d.y, self.height))
+    }
+
+    /// Indices of the points at most `radius` away from `position`.
+    pub fn query_radius(&self, position: Vec2, radius: f32) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.for_each_within(position, radius, |i, _| found.push(i));
+        found
+    }
+
+    /// Every pair `(i, j)` with `i < j` of points at most `r` apart.
+    pub fn pairs_within(&self, r: f32) -> Vec<(usize, usize)> {
+        if self.wrap {
+            let mut pairs = Vec::new();
+            for &(i, position) in &self.entries {
+                self.for_each_within(position, r, |j, _| {
+                    if (i as usize) < j {
+                        pairs.push((i as usize, j));
+                    }
+                });
+            }
+            return pairs;
+        }
+
+        let r_squared = r * r;
+        let (reach_x, reach_y) = self.reach(r);
+        let mut pairs = Vec::new();
+
+        let mut check = |(i, a): (u32, Vec2), &(j, b): &(u32, Vec2)| {
+            if (a - b).length_squared() <= r_squared {
+                pairs.push((i.min(j) as usize, i.max(j) as usize));
+            }
+        };
+
+        // only look forward, at the rest of the current row and the rows
+        // below, so every pair is visited once
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let cell = row * self.columns + column;
+                let (x0, x1) = (
+                    column.saturating_sub(reach_x),
+                    (column + reach_x).min(self.columns - 1),
+                );
+                let row_end = self.cell_start[row * self.columns + x1 + 1] as usize;
+
+                for k in self.cell_start[cell] as usize..self.cell_start[cell + 1] as usize {
+                    let entry = self.entries[k];
+
+                    self.entries[k + 1..row_end]
+                        .iter()
+                        .for_each(|other| check(entry, other));
+
+                    for y in (row + 1)..=(row + reach_y).min(self.rows - 1) {
+                        self.cells(y * self.columns + x0, y * self.columns + x1)
+                            .iter()
+                            .for_each(|other| check(entry, other));
+                    }
+                }
+            }
+        }
+
+        pairs
+    }
+
+    /// Points in the cells `first..=last`.
+    fn cells(&self, first: usize, last: usize) -> &[(u32, Vec2)] {
+        let (start, end) = (self.cell_start[first], self.cell_start[last + 1]);
+        &self.entries[start as usize..end as usize]
+    }
+
+    fn cell_of(&self, position: Vec2) -> usize {
+        let (column, row) = self.coords_of(position);
+        row * self.columns + column
+    }
+
+    fn coords_of(&self, position: Vec2) -> (usize, usize) {
+        let position = if self.wrap {
+            Vec2::new(
+                position.x.rem_euclid(self.width),
+                position.y.rem_euclid(self.height),
+            )
+        } else {
+            position
+        };
+
+        // the float to usize cast saturates, so negative coordinates land on 0
+        let column = ((position.x / self.cell_width) as usize).min(self.columns - 1);
+        let row = ((position.y / self.cell_height) as usize).min(self.rows - 1);
+        (column, row)
+    }
+
+    /// Cells a `radius` spans on each side, never more than the grid has so
+    /// huge or infinite radii cannot overflow the arithmetic on cells.
+    fn reach(&self, radius: f32) -> (usize, usize) {
+        (
+            ((radius / self.cell_width).ceil() as usize).min(self.columns),
+            ((radius / self.cell_height).ceil() as usize).min(self.rows),
+        )
+    }
+}
+
+/// Cells `center - reach..=center + reach` of a wrapping axis with `len`
+/// cells, as at most two non overlapping ranges.
+fn wrapped_spans(center: usize, reach: usize, len: usize) -> [Option<(usize, usize)>; 2] {
+    if 2 * reach + 1 >= len {
+        return [Some((0, len - 1)), None];
+    }
+
+    let (first, last) = (center as isize - reach as isize, center + reach);
+    if first < 0 {
+        [
+            Some((0, last)),
+            Some(((len as isize + first) as usize, len - 1)),
+        ]
+    } else if last >= len {
+        [Some((first as usize, len - 1)), Some((0, last - len))]
+    } else {
+        [Some((first as usize, last)), None]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rng;
+
+    fn random_points(count: usize) -> Vec<Vec2> {
+        let mut rng = Rng::new(11);
+        (0..count)
+            .map(|_| Vec2::new(rng.range(-5.0, 205.0), rng.range(-5.0, 105.0)))
+            .collect()
+    }
+
+    #[test]
+    fn query_radius_matches_brute_force() {
+        let points = random_points(500);
+        let mut grid = SpatialGrid::new(200.0, 100.0, 10.0);
+        grid.rebuild(points.iter().copied());
+
+        let center = Vec2::new(42.0, 58.0);
+        for radius in [3.0, 10.0, 27.5] {
+            let mut found = grid.query_radius(center, radius);
+            found.sort_unstable();
+
+            let expected = (0..points.len())
+                .filter(|&i| (points[i] - center).length_squared() <= radius * radius)
+                .collect::<Vec<_>>();
+
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn pairs_within_matches_brute_force() {
+        let points = random_points(300);
+        let mut grid = SpatialGrid::new(200.0, 100.0, 8.0);
+        grid.rebuild(points.iter().copied());
+
+        let mut pairs = grid.pairs_within(8.0);
+        pairs.sort_unstable();
+
+        let mut expected = Vec::new();
+        for i in 0..points.len() {
+            for j in (i + 1)..points.len() {
+                if (points[i] - points[j]).length_squared() <= 64.0 {
+                    expected.push((i, j));
+                }
+            }
+        }
+
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "the cell size must be finite and positive")]
+    fn rejects_empty_cells() {
+        SpatialGrid::new(200.0, 100.0, 0.0);
+    }
+
+    #[test]
+    fn tiny_cells_are_capped() {
+        let mut grid = SpatialGrid::new(200.0, 100.0, 1e-6);
+        grid.rebuild(random_points(50));
+
+        assert_eq!(grid.query_radius(Vec2::new(100.0, 50.0), 500.0).len(), 50);
+    }
+
+    #[test]
+    fn infinite_radii_find_everything() {
+        let points = random_points(50);
+        for mut grid in [
+            SpatialGrid::new(200.0, 100.0, 10.0),
+            SpatialGrid::wrapping(200.0, 100.0, 10.0),
+        ] {
+            grid.rebuild(points.iter().copied());
+
+            let center = Vec2::new(100.0, 50.0);
+            assert_eq!(grid.query_radius(center, f32::INFINITY).len(), 50);
+            assert_eq!(grid.pairs_within(f32::INFINITY).len(), 50 * 49 / 2);
+        }
+    }
+
+    #[test]
+    fn wrapping_queries_see_across_the_edges() {
+        let points = [
+            Vec2::new(1.0, 1.0),
+            Vec2::new(199.0, 99.0),
+            Vec2::new(100.0, 50.0),
+            Vec2::new(195.0, 3.0),
+        ];
+        let mut grid = SpatialGrid::wrapping(200.0, 100.0, 10.0);
+        grid.rebuild(points.iter().copied());
+
+        let mut found = Vec::new();
+        grid.for_each_within(Vec2::new(0.0, 0.0), 6.0, |i, position| {
+            found.push((i, position))
+        });
+        found.sort_by_key(|&(i, _)| i);
+
+        assert_eq!(
+            found,
+            vec![
+                (0, Vec2::new(1.0, 1.0)),
+                (1, Vec2::new(-1.0, -1.0)),
+                (3, Vec2::new(-5.0, 3.0)),
+            ]
+        );
+
+        let mut pairs = grid.pairs_within(6.0);
+        pairs.sort_unstable();
+        assert_eq!(pairs, vec![(0, 1), (1, 3)]);
+    }
+}
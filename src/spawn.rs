@@ -0,0 +1,345 @@
+use std::path::Path;
+
+use anyhow::{Context as _, Result};
+
+use crate::{Rng, SpatialGrid, Species, Vec2};
+
+/// How [`WorldBuilder`](crate::WorldBuilder) places the initial population.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum SpawnPattern {
+    /// Uniformly at random over the arena.
+    #[default]
+    Uniform,
+    /// Uniformly at random, but no two agents closer than `min_distance`
+    /// unless the arena is too crowded for it.
+    PoissonDisk { min_distance: f32 },
+    /// Every species around its own random center, normally distributed
+    /// with a standard deviation of `spread`.
+    Clusters { spread: f32 },
+    /// Every species on its own ring around the center of the arena, the
+    /// first species on the innermost one.
+    Rings,
+    /// Every species in its own vertical stripe, the first one on the left.
+    Stripes,
+    /// On the pixels of an image matching the color of the species, the
+    /// image is stretched over the arena.
+    Mask(SpawnMask),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnMask {
+    pub image: image::RgbaImage,
+    /// Species spawned on the opaque pixels of each color.
+    pub colors: Vec<([u8; 3], Species)>,
+}
+
+impl SpawnMask {
+    pub fn new(image: image::RgbaImage, colors: Vec<([u8; 3], Species)>) -> Self {
+        Self { image, colors }
+    }
+
+    pub fn load<P>(filepath: P, colors: Vec<([u8; 3], Species)>) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        let image = image::open(filepath)
+            .with_context(|| format!("Could not read spawn mask {}", filepath.display()))?;
+
+        Ok(Self::new(image.to_rgba8(), colors))
+    }
+}
+
+/// Picks spawn positions one agent at a time following a [`SpawnPattern`].
+#[derive(Debug, Clone)]
+pub struct Spawner<'a> {
+    pattern: &'a SpawnPattern,
+    width: f32,
+    height: f32,
+    species: usize,
+    /// Cluster centers, indexed by species.
+    centers: Vec<Vec2>,
+    /// Mask pixels, indexed by species.
+    pixels: Vec<Vec<(u32, u32)>>,
+    /// Agents placed so far, only kept for [`SpawnPattern::PoissonDisk`].
+    placed: Option<Placed>,
+}
+
+impl<'a> Spawner<'a> {
+    /// Positions a failing `is_free` check are drawn again this many times
+    /// before being accepted anyway.
+    pub const MAX_ATTEMPTS: usize = 100;
+
+    /// Spawner over a `width` by `height` arena shared by `species` species,
+    /// only [`SpawnPattern::Clusters`] draws from `rng` up front.
+    pub fn new(
+        pattern: &'a SpawnPattern,
+        width: f32,
+        height: f32,
+        species: usize,
+        rng: &mut Rng,
+    ) -> Self {
+        let mut spawner = Self {
+            pattern,
+            width,
+            height,
+            species,
+            centers: Vec::new(),
+            pixels: Vec::new(),
+            placed: None,
+        };
+
+        match pattern {
+            // NaN, zero or negative distances leave every position spaced
+            SpawnPattern::PoissonDisk { min_distance } if *min_distance > 0.0 => {
+                spawner.placed = Some(Placed::new(width, height, *min_distance));
+            }
+            SpawnPattern::Clusters { .. } => {
+                spawner.centers = (0..species).map(|_| spawner.uniform(rng)).collect();
+            }
+            SpawnPattern::Mask(mask) => {
+                spawner.pixels = vec![Vec::new(); species];
+                for (x, y, pixel) in mask.image.enumerate_pixels() {
+                    let [r, g, b, a] = pixel.0;
+                    let matches = mask.colors.iter().filter(|(color, _)| *color == [r, g, b]);
+                    for &(_, kind) in matches {
+                        if a > 0 && kind.index() < species {
+                            spawner.pixels[kind.index()].push((x, y));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        spawner
+    }
+
+    /// Position of the next agent of `species`, drawn again while `is_free`
+    /// rejects it.
+    pub fn position<F>(&mut self, rng: &mut Rng, species: Species, is_free: F) -> Vec2
+    where
+        F: Fn(Vec2) -> bool,
+    {
+        let mut position = Vec2::ZERO;
+        for _ in 0..Self::MAX_ATTEMPTS {
+            position = self.sample(rng, species);
+            if is_free(position) && self.is_spaced(position) {
+                break;
+            }
+        }
+
+        if let Some(placed) = &mut self.placed {
+            placed.insert(position);
+        }
+        position
+    }
+
+    fn is_spaced(&self, position: Vec2) -> bool {
+        self.placed
+            .as_ref()
+            .is_none_or(|placed| placed.is_spaced(position))
+    }
+
+    fn uniform(&self, rng: &mut Rng) -> Vec2 {
+        Vec2::new(rng.range(0.0, self.width), rng.range(0.0, self.height))
+    }
+
+    fn sample(&self, rng: &mut Rng, species: Species) -> Vec2 {
+        let (width, height) = (self.width, self.height);
+        let slots = self.species.max(1) as f32;
+        let slot = species.index() as f32;
+
+        match self.pattern {
+            SpawnPattern::Uniform | SpawnPattern::PoissonDisk { .. } => self.uniform(rng),
+            SpawnPattern::Clusters { spread } => {
+                let offset = Vec2::new(rng.normal(), rng.normal()) * *spread;
+                let position = self.centers[species.index()] + offset;
+                Vec2::new(position.x.clamp(0.0, width), position.y.clamp(0.0, height))
+            }
+            SpawnPattern::Rings => {
+                let radius = width.min(height) / 2.0 * (slot + 1.0) / (slots + 1.0);
+                Vec2::new(width / 2.0, height / 2.0) + rng.unit_vector() * radius
+            }
+            SpawnPattern::Stripes => {
+                let stripe = width / slots;
+                Vec2::new(
+                    rng.range(slot * stripe, (slot + 1.0) * stripe),
+                    rng.range(0.0, height),
+                )
+            }
+            SpawnPattern::Mask(mask) => {
+                // species without any pixel of their color spawn anywhere
+                let pixels = &self.pixels[species.index()];
+                if pixels.is_empty() {
+                    return self.uniform(rng);
+                }
+
+                let (x, y) = pixels[rng.below(pixels.len())];
+                let (image_width, image_height) = mask.image.dimensions();
+                Vec2::new(
+                    (x as f32 + rng.next_f32()) * width / image_width as f32,
+                    (y as f32 + rng.next_f32()) * height / image_height as f32,
+                )
+            }
+        }
+    }
+}
+
+/// Positions of the agents placed so far, bucketed into cells about
+/// `min_distance / √2` wide so a candidate is only checked against the few
+/// cells around it instead of every agent.
+#[derive(Debug, Clone)]
+struct Placed {
+    min_distance: f32,
+    columns: usize,
+    rows: usize,
+    cell_width: f32,
+    cell_height: f32,
+    /// One plus the last position placed in every cell, 0 for empty cells.
+    heads: Vec<u32>,
+    /// One plus the position placed before each one in the same cell, 0 for
+    /// the first one.
+    next: Vec<u32>,
+    positions: Vec<Vec2>,
+}
+
+impl Placed {
+    fn new(width: f32, height: f32, min_distance: f32) -> Self {
+        let cell_size = min_distance / std::f32::consts::SQRT_2;
+        let cells =
+            |length: f32| ((length / cell_size) as usize).clamp(1, SpatialGrid::MAX_CELLS_PER_SIDE);
+        let (columns, rows) = (cells(width), cells(height));
+
+        Self {
+            min_distance,
+            columns,
+            rows,
+            cell_width: width / columns as f32,
+            cell_height: height / rows as f32,
+            heads: vec![0; columns * rows],
+            next: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, position: Vec2) {
+        let cell = self.cell_of(position);
+        self.positions.push(position);
+        self.next.push(self.heads[cell]);
+        self.heads[cell] = self.positions.len() as u32;
+    }
+
+    fn is_spaced(&self, position: Vec2) -> bool {
+        let (column, row) = self.coords_of(position);
+        // cells are never narrower than `min_distance / √2`, so this is at
+        // most 2 unless the arena is narrower than that
+        let reach =
+            |length: f32, cells: usize| ((self.min_distance / length).ceil() as usize).min(cells);
+        let (reach_x, reach_y) = (
+            reach(self.cell_width, self.columns),
+            reach(self.cell_height, self.rows),
+        );
+        let min_squared = self.min_distance * self.min_distance;
+
+        for row in row.saturating_sub(reach_y)..=(row + reach_y).min(self.rows - 1) {
+            for column in column.saturating_sub(reach_x)..=(column + reach_x).min(self.columns - 1)
+            {
+                let mut next = self.heads[row * self.columns + column];
+                while next > 0 {
+                    let other = next as usize - 1;
+                    if (self.positions[other] - position).length_squared() < min_squared {
+                        return false;
+                    }
+                    next = self.next[other];
+                }
+            }
+        }
+        true
+    }
+
+    fn cell_of(&self, position: Vec2) -> usize {
+        let (column, row) = self.coords_of(position);
+        row * self.columns + column
+    }
+
+    fn coords_of(&self, position: Vec2) -> (usize, usize) {
+        // the float to usize cast saturates, so negative coordinates land on 0
+        let column = ((position.x / self.cell_width) as usize).min(self.columns - 1);
+        let row = ((position.y / self.cell_height) as usize).min(self.rows - 1);
+        (column, row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn(pattern: &SpawnPattern, counts: &[usize]) -> Vec<(Species, Vec2)> {
+        let mut rng = Rng::new(1);
+        let mut spawner = Spawner::new(pattern, 300.0, 200.0, counts.len(), &mut rng);
+
+        let mut agents = Vec::new();
+        for (i, &count) in counts.iter().enumerate() {
+            for _ in 0..count {
+                let species = Species(i as u8);
+                agents.push((species, spawner.position(&mut rng, species, |_| true)));
+            }
+        }
+        agents
+    }
+
+    #[test]
+    fn patterns_place_species_apart() {
+        for (species, position) in spawn(&SpawnPattern::Stripes, &[20, 20, 20]) {
+            let stripe = species.index() as f32 * 100.0;
+            assert!((stripe..stripe + 100.0).contains(&position.x));
+        }
+
+        for (species, position) in spawn(&SpawnPattern::Rings, &[20, 20]) {
+            let radius = (position - Vec2::new(150.0, 100.0)).length();
+            let expected = 100.0 * (species.index() as f32 + 1.0) / 3.0;
+            assert!((radius - expected).abs() < 1e-3);
+        }
+
+        let agents = spawn(&SpawnPattern::PoissonDisk { min_distance: 15.0 }, &[50, 50]);
+        for (i, (_, a)) in agents.iter().enumerate() {
+            for (_, b) in &agents[i + 1..] {
+                assert!((*a - *b).length() >= 15.0);
+            }
+        }
+    }
+
+    #[test]
+    fn poisson_disk_handles_any_distance() {
+        // more agents than fit, the last ones are accepted anyway
+        let agents = spawn(&SpawnPattern::PoissonDisk { min_distance: 60.0 }, &[40]);
+        assert_eq!(agents.len(), 40);
+
+        for min_distance in [0.0, 1e-30, f32::INFINITY, f32::NAN] {
+            let agents = spawn(&SpawnPattern::PoissonDisk { min_distance }, &[10, 10]);
+            for (_, position) in agents {
+                assert!((0.0..=300.0).contains(&position.x));
+                assert!((0.0..=200.0).contains(&position.y));
+            }
+        }
+    }
+
+    #[test]
+    fn masks_map_colors_to_species() {
+        // red on the left half, blue on the right one
+        let image = image::RgbaImage::from_fn(4, 2, |x, _| match x {
+            0 | 1 => image::Rgba([255, 0, 0, 255]),
+            _ => image::Rgba([0, 0, 255, 255]),
+        });
+        let mask = SpawnMask::new(
+            image,
+            vec![([255, 0, 0], Species(0)), ([0, 0, 255], Species(1))],
+        );
+
+        for (species, position) in spawn(&SpawnPattern::Mask(mask), &[30, 30]) {
+            assert_eq!(position.x < 150.0, species == Species(0));
+            assert!((0.0..200.0).contains(&position.y));
+        }
+    }
+}
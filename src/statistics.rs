@@ -0,0 +1,177 @@
+use std::{io::Write, path::Path};
+
+use anyhow::{Context as _, Result};
+
+use crate::{Agent, Ruleset};
+
+/// State of the world at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub tick: u64,
+    /// Simulated time in seconds.
+    pub elapsed: f64,
+    /// Number of agents of every species, indexed by species.
+    pub populations: Vec<usize>,
+    /// Agents converted, or eaten in an ecology, since the previous sample.
+    pub conversions: u64,
+    /// Agents that mutated into another species since the previous sample.
+    pub mutations: u64,
+    /// Mean speed over all agents, in units per second.
+    pub mean_speed: f32,
+}
+
+/// Population time series recorded by a [`World`](crate::World) every
+/// `interval` ticks, plus a last sample when the run finishes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    interval: u64,
+    species: Vec<String>,
+    samples: Vec<Sample>,
+    conversions: u64,
+    mutations: u64,
+}
+
+impl Statistics {
+    pub fn new(interval: u64, ruleset: &Ruleset) -> Self {
+        Self {
+            interval: interval.max(1),
+            species: ruleset
+                .species()
+                .map(|species| ruleset.name(species).to_owned())
+                .collect(),
+            samples: Vec::new(),
+            conversions: 0,
+            mutations: 0,
+        }
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Names of the species, in the order of the populations.
+    pub fn species(&self) -> &[String] {
+        &self.species
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.conversions = 0;
+        self.mutations = 0;
+    }
+
+    /// Adds conversions to the count of the next sample.
+    pub fn count_conversions(&mut self, conversions: usize) {
+        self.conversions += conversions as u64;
+    }
+
+    /// Adds mutations to the count of the next sample.
+    pub fn count_mutations(&mut self, mutations: usize) {
+        self.mutations += mutations as u64;
+    }
+
+    pub fn record(&mut self, tick: u64, elapsed: f64, agents: &[Agent]) {
+        let mut populations = vec![0; self.species.len()];
+        let mut total_speed = 0.0;
+        for agent in agents {
+            populations[agent.species.index()] += 1;
+            total_speed += agent.velocity.length();
+        }
+
+        self.samples.push(Sample {
+            tick,
+            elapsed,
+            populations,
+            conversions: std::mem::take(&mut self.conversions),
+            mutations: std::mem::take(&mut self.mutations),
+            mean_speed: if agents.is_empty() {
+                0.0
+            } else {
+                total_speed / agents.len() as f32
+            },
+        });
+    }
+
+    /// Writes one row per sample, with a column per species named after it.
+    pub fn write_csv<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        write!(writer, "tick,time")?;
+        for name in &self.species {
+            write!(writer, ",{name}")?;
+        }
+        writeln!(writer, ",conversions,mutations,mean_speed")?;
+
+        for sample in &self.samples {
+            write!(writer, "{},{}", sample.tick, sample.elapsed)?;
+            for count in &sample.populations {
+                write!(writer, ",{count}")?;
+            }
+            writeln!(
+                writer,
+                ",{},{},{}",
+                sample.conversions, sample.mutations, sample.mean_speed
+            )?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn save_csv<P>(&self, filepath: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        let file = std::fs::File::create(filepath)
+            .with_context(|| format!("Could not create {}", filepath.display()))?;
+
+        self.write_csv(std::io::BufWriter::new(file))
+            .with_context(|| format!("Could not write statistics to {}", filepath.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Shape, WorldBuilder};
+
+    #[test]
+    fn records_every_interval_and_at_the_finish() {
+        let mut world = WorldBuilder::new()
+            .seed(4)
+            .statistics(10)
+            .population(Shape::Rock, 20)
+            .population(Shape::Scissors, 20)
+            .build()
+            .unwrap();
+
+        while world.step(0.1).is_none() {}
+
+        let statistics = world.statistics().unwrap();
+        let samples = statistics.samples();
+        let last = samples.last().unwrap();
+
+        assert_eq!(samples[0].tick, 0);
+        assert_eq!(samples[0].populations, vec![20, 0, 20]);
+        assert_eq!(samples[1].tick, 10);
+        assert_eq!(last.tick, world.tick());
+        assert_eq!(last.populations, vec![40, 0, 0]);
+        assert_eq!(samples.iter().map(|s| s.conversions).sum::<u64>(), 20);
+
+        let mut csv = Vec::new();
+        statistics.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+
+        assert_eq!(
+            lines.next(),
+            Some("tick,time,rock,paper,scissors,conversions,mutations,mean_speed")
+        );
+        assert!(lines.next().unwrap().starts_with("0,0,20,0,20,0,0,"));
+        assert_eq!(lines.count(), samples.len() - 1);
+    }
+}
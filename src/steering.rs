@@ -0,0 +1,121 @@
+use crate::{Rad, Vec2};
+
+/// Chase-and-flee parameters of a shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Steering {
+    /// How strongly the agent turns toward the nearest shape it beats.
+    pub chase_weight: f32,
+    /// How strongly the agent turns away from the nearest shape that beats it.
+    pub flee_weight: f32,
+    pub max_speed: f32,
+    /// Radians per second.
+    pub max_turn_rate: f32,
+    /// Agents further away than this are ignored.
+    pub sensing_radius: f32,
+    /// How strongly the agent turns away from obstacles within its sensing
+    /// radius, scaled up as they get closer.
+    pub avoid_weight: f32,
+}
+
+impl Steering {
+    /// Turns `velocity` toward `prey` and away from `predator` and the
+    /// closest point of an `obstacle`, all given as offsets from the agent,
+    /// and caps the result at the maximum speed.
+    pub fn steer(
+        &self,
+        velocity: Vec2,
+        prey: Option<Vec2>,
+        predator: Option<Vec2>,
+        obstacle: Option<Vec2>,
+        dt: f32,
+    ) -> Vec2 {
+        let mut desired = velocity.normalize_or_zero();
+        if let Some(offset) = prey {
+            desired += offset.normalize_or_zero() * self.chase_weight;
+        }
+        if let Some(offset) = predator {
+            desired -= offset.normalize_or_zero() * self.flee_weight;
+        }
+        if let Some(offset) = obstacle {
+            desired += self.avoidance(offset);
+        }
+
+        self.turn(velocity, desired, dt)
+    }
+
+    /// Push away from the closest point of an obstacle at `offset`,
+    /// stronger the closer it is.
+    pub fn avoidance(&self, offset: Vec2) -> Vec2 {
+        let closeness = 1.0 - (offset.length() / self.sensing_radius).min(1.0);
+        -(offset.normalize_or_zero() * (self.avoid_weight * closeness))
+    }
+
+    /// Turns `velocity` toward the `desired` direction as fast as the turn
+    /// rate allows and caps the result at the maximum speed.
+    pub fn turn(&self, velocity: Vec2, desired: Vec2, dt: f32) -> Vec2 {
+        let mut velocity = velocity;
+        if desired != Vec2::ZERO && velocity != Vec2::ZERO {
+            let max_turn = self.max_turn_rate * dt;
+            let angle = velocity.angle_to(desired).0.clamp(-max_turn, max_turn);
+            velocity = velocity.rotate(Rad(angle));
+        }
+
+        let speed = velocity.length();
+        if speed > self.max_speed {
+            velocity *= self.max_speed / speed;
+        }
+
+        velocity
+    }
+}
+
+impl Default for Steering {
+    fn default() -> Self {
+        Self {
+            chase_weight: 1.0,
+            flee_weight: 1.0,
+            max_speed: 60.0,
+            max_turn_rate: std::f32::consts::PI,
+            sensing_radius: 80.0,
+            avoid_weight: 2.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_toward_prey_and_away_from_predators() {
+        let steering = Steering::default();
+        let velocity = Vec2::new(10.0, 0.0);
+
+        let chasing = steering.steer(velocity, Some(Vec2::new(0.0, 50.0)), None, None, 0.1);
+        let fleeing = steering.steer(velocity, None, Some(Vec2::new(0.0, 50.0)), None, 0.1);
+
+        assert!(chasing.y > 0.0);
+        assert!(fleeing.y < 0.0);
+        assert!((chasing.length() - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn turn_rate_and_speed_are_capped() {
+        let steering = Steering {
+            max_speed: 5.0,
+            max_turn_rate: 1.0,
+            ..Default::default()
+        };
+
+        let velocity = steering.steer(
+            Vec2::new(10.0, 0.0),
+            Some(Vec2::new(0.0, 1.0)),
+            None,
+            None,
+            0.1,
+        );
+
+        assert!((velocity.length() - 5.0).abs() < 1e-4);
+        assert!((Vec2::new(1.0, 0.0).angle_to(velocity).0 - 0.1).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,235 @@
+use std::{io::Write, path::Path, str::FromStr};
+
+use anyhow::{anyhow, ensure, Context as _, Result};
+
+use crate::{Batch, BatchReport, Species};
+
+/// A scenario parameter and the values it takes during a [`Sweep`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepAxis {
+    /// Name understood by [`Scenario::set_param`](crate::Scenario::set_param).
+    pub parameter: String,
+    pub values: Vec<f64>,
+}
+
+impl SweepAxis {
+    /// `steps` evenly spaced values from `start` to `end`, both included.
+    pub fn linear<S>(parameter: S, start: f64, end: f64, steps: usize) -> Self
+    where
+        S: Into<String>,
+    {
+        let values = match steps {
+            0 => Vec::new(),
+            1 => vec![start],
+            _ => (0..steps)
+                .map(|i| start + (end - start) * i as f64 / (steps - 1) as f64)
+                .collect(),
+        };
+
+        Self {
+            parameter: parameter.into(),
+            values,
+        }
+    }
+}
+
+/// Parses `PARAMETER=START:END:STEPS`.
+impl FromStr for SweepAxis {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let error = || anyhow!("expected PARAMETER=START:END:STEPS, got {s:?}");
+
+        let (parameter, range) = s.split_once('=').ok_or_else(error)?;
+        let range = range.split(':').collect::<Vec<_>>();
+        let [start, end, steps] = range.as_slice() else {
+            return Err(error());
+        };
+
+        Ok(Self::linear(
+            parameter,
+            start.parse().map_err(|_| error())?,
+            end.parse().map_err(|_| error())?,
+            steps.parse().map_err(|_| error())?,
+        ))
+    }
+}
+
+/// Runs a [`Batch`] for every combination of the values of one or two
+/// parameters.
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    pub batch: Batch,
+    pub axes: Vec<SweepAxis>,
+}
+
+impl Sweep {
+    pub fn new(batch: Batch) -> Self {
+        Self {
+            batch,
+            axes: Vec::new(),
+        }
+    }
+
+    pub fn axis(mut self, axis: SweepAxis) -> Self {
+        self.axes.push(axis);
+        self
+    }
+
+    /// Runs the grid, the first axis varying slowest.
+    pub fn run(&self) -> Result<SweepReport> {
+        ensure!(
+            (1..=2).contains(&self.axes.len()),
+            "a sweep needs one or two parameters"
+        );
+
+        let mut points = vec![Vec::new()];
+        for axis in &self.axes {
+            points = points
+                .into_iter()
+                .flat_map(|point| {
+                    axis.values.iter().map(move |&value| {
+                        let mut point = point.clone();
+                        point.push(value);
+                        point
+                    })
+                })
+                .collect();
+        }
+
+        let mut cells = Vec::with_capacity(points.len());
+        for point in points {
+            let mut batch = self.batch.clone();
+            for (axis, &value) in self.axes.iter().zip(&point) {
+                batch
+                    .scenario
+                    .set_param(&axis.parameter, value)
+                    .with_context(|| format!("Could not sweep {}", axis.parameter))?;
+            }
+
+            cells.push((point, batch.run()?));
+        }
+
+        Ok(SweepReport {
+            parameters: self.axes.iter().map(|a| a.parameter.clone()).collect(),
+            cells,
+        })
+    }
+}
+
+/// Outcomes of a [`Sweep`], one batch per combination of parameter values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepReport {
+    pub parameters: Vec<String>,
+    pub cells: Vec<(Vec<f64>, BatchReport)>,
+}
+
+impl SweepReport {
+    /// Writes one row per combination of parameter values with the win
+    /// probability of every species, its confidence interval and the mean
+    /// time of the finished runs.
+    pub fn write_csv<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let Some((_, first)) = self.cells.first() else {
+            return Ok(());
+        };
+
+        write!(writer, "{}", self.parameters.join(","))?;
+        write!(writer, ",runs,finished")?;
+        for name in &first.species {
+            write!(writer, ",{name}_win,{name}_win_low,{name}_win_high")?;
+        }
+        writeln!(writer, ",mean_finish_time")?;
+
+        for (point, report) in &self.cells {
+            for value in point {
+                write!(writer, "{value},")?;
+            }
+
+            let finished = report
+                .outcomes
+                .iter()
+                .filter(|outcome| outcome.finished)
+                .map(|outcome| outcome.elapsed)
+                .collect::<Vec<_>>();
+            write!(writer, "{},{}", report.outcomes.len(), finished.len())?;
+
+            for i in 0..report.species.len() {
+                let (p, low, high) = report.win_probability(Species(i as u8));
+                write!(writer, ",{p},{low},{high}")?;
+            }
+
+            if finished.is_empty() {
+                writeln!(writer, ",")?;
+            } else {
+                let mean = finished.iter().sum::<f64>() / finished.len() as f64;
+                writeln!(writer, ",{mean}")?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    pub fn save_csv<P>(&self, filepath: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        let file = std::fs::File::create(filepath)
+            .with_context(|| format!("Could not create {}", filepath.display()))?;
+
+        self.write_csv(std::io::BufWriter::new(file))
+            .with_context(|| format!("Could not write the sweep to {}", filepath.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ruleset, Scenario};
+
+    #[test]
+    fn parses_axes() {
+        let axis = "species.rock.speed=20:60:5".parse::<SweepAxis>().unwrap();
+
+        assert_eq!(axis.parameter, "species.rock.speed");
+        assert_eq!(axis.values, vec![20.0, 30.0, 40.0, 50.0, 60.0]);
+        assert!("speed=1:2".parse::<SweepAxis>().is_err());
+    }
+
+    #[test]
+    fn sweeps_a_grid() {
+        let batch = Batch::new(Scenario::from_ruleset(Ruleset::classic(), 4))
+            .runs(2)
+            .max_steps(200);
+        let report = Sweep::new(batch)
+            .axis(SweepAxis::linear("species.rock.speed", 30.0, 60.0, 2))
+            .axis(SweepAxis::linear("species.paper.ratio", 0.2, 0.6, 3))
+            .run()
+            .unwrap();
+
+        assert_eq!(report.cells.len(), 6);
+        assert_eq!(report.cells[1].0, vec![30.0, 0.4]);
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        assert!(csv.starts_with(
+            "species.rock.speed,species.paper.ratio,runs,finished,rock_win,rock_win_low,"
+        ));
+        assert_eq!(csv.lines().count(), 7);
+
+        let batch = Batch::new(Scenario::from_ruleset(Ruleset::classic(), 4)).runs(2);
+        let error = Sweep::new(batch)
+            .axis(SweepAxis::linear("contact_radius", 0.0, 16.0, 2))
+            .run()
+            .unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "Could not sweep contact_radius: contact_radius must be positive, not 0"
+        );
+    }
+}
@@ -0,0 +1,1865 @@
+mod snapshot;
+
+pub use self::snapshot::*;
+
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Result};
+
+use crate::{
+    Arena, Behavior, BehaviorId, Boundary, ChaseFlee, Combat, DeathCause, Ecology, FightModel,
+    FightResults, FightTiming, Finish, Mutation, Neighbour, Neighbourhood, Obstacle, Rng, Ruleset,
+    SimulationEvent, SimulationState, SpatialGrid, SpawnPattern, Spawner, Species, Stalemate,
+    Statistics, Steering, TieBreak, Vec2,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Agent {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub species: Species,
+    /// From 1 when unhurt down to 0, only used by [`Combat`].
+    pub health: f32,
+    /// Seconds until the agent can hit again, only used by [`Combat`].
+    pub cooldown: f32,
+    /// The agent dies once it runs out, only used by [`Ecology`].
+    pub energy: f32,
+    /// Overrides the behaviour of its species, see [`World::add_behavior`].
+    pub behavior: Option<BehaviorId>,
+}
+
+impl Agent {
+    pub fn new<P, V, S>(position: P, velocity: V, species: S) -> Self
+    where
+        P: Into<Vec2>,
+        V: Into<Vec2>,
+        S: Into<Species>,
+    {
+        Self {
+            position: position.into(),
+            velocity: velocity.into(),
+            species: species.into(),
+            health: 1.0,
+            cooldown: 0.0,
+            energy: 1.0,
+            behavior: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorldBuilder {
+    pub width: f32,
+    pub height: f32,
+    pub boundary: Boundary,
+    pub seed: u64,
+    pub jitter: f32,
+    pub contact_radius: f32,
+    pub stalemate: Stalemate,
+    pub obstacles: Vec<Obstacle>,
+    pub line_of_sight: bool,
+    pub fight: FightModel,
+    pub mutation: Mutation,
+    pub ecology: Option<Ecology>,
+    /// Interval in ticks between two [`Statistics`] samples, `None` records nothing.
+    pub statistics: Option<u64>,
+    /// Whether the world queues [`SimulationEvent`]s.
+    pub events: bool,
+    pub ruleset: Ruleset,
+    pub steering: Vec<(Species, Steering)>,
+    pub behaviors: Vec<(Species, Arc<dyn Behavior>)>,
+    pub population: Vec<(Species, usize)>,
+    pub spawn: SpawnPattern,
+}
+
+impl WorldBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn size(mut self, width: f32, height: f32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Strength of the random steering applied to every agent, in units per second.
+    pub fn jitter(mut self, jitter: f32) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn contact_radius(mut self, contact_radius: f32) -> Self {
+        self.contact_radius = contact_radius;
+        self
+    }
+
+    pub fn stalemate(mut self, stalemate: Stalemate) -> Self {
+        self.stalemate = stalemate;
+        self
+    }
+
+    pub fn obstacle(mut self, obstacle: Obstacle) -> Self {
+        self.obstacles.push(obstacle);
+        self
+    }
+
+    /// Whether obstacles hide agents from each other.
+    pub fn line_of_sight(mut self, line_of_sight: bool) -> Self {
+        self.line_of_sight = line_of_sight;
+        self
+    }
+
+    pub fn fight(mut self, fight: FightModel) -> Self {
+        self.fight = fight;
+        self
+    }
+
+    pub fn mutation(mut self, mutation: Mutation) -> Self {
+        self.mutation = mutation;
+        self
+    }
+
+    pub fn ecology(mut self, ecology: Ecology) -> Self {
+        self.ecology = Some(ecology);
+        self
+    }
+
+    pub fn statistics(mut self, interval: u64) -> Self {
+        self.statistics = Some(interval);
+        self
+    }
+
+    /// Queues events from the first step on, agents spawned with the world
+    /// are not reported.
+    pub fn events(mut self) -> Self {
+        self.events = true;
+        self
+    }
+
+    pub fn ruleset(mut self, ruleset: Ruleset) -> Self {
+        self.ruleset = ruleset;
+        self
+    }
+
+    pub fn steering<S>(mut self, species: S, steering: Steering) -> Self
+    where
+        S: Into<Species>,
+    {
+        self.steering.push((species.into(), steering));
+        self
+    }
+
+    pub fn behavior<S, B>(mut self, species: S, behavior: B) -> Self
+    where
+        S: Into<Species>,
+        B: Behavior + 'static,
+    {
+        self.behaviors.push((species.into(), Arc::new(behavior)));
+        self
+    }
+
+    pub fn population<S>(mut self, species: S, count: usize) -> Self
+    where
+        S: Into<Species>,
+    {
+        self.population.push((species.into(), count));
+        self
+    }
+
+    /// How the population is placed, uniformly at random by default.
+    pub fn spawn(mut self, pattern: SpawnPattern) -> Self {
+        self.spawn = pattern;
+        self
+    }
+
+    /// Creates the world and spawns the requested population following the
+    /// spawn pattern, outside the obstacles, heading in random directions at
+    /// their maximum speed.
+    ///
+    /// Fails if the arena has no area, the contact radius is not positive, or
+    /// if a species, the win matrix or the mutation matrix does not belong
+    /// to the ruleset.
+    pub fn build(self) -> Result<World> {
+        Arena::new(self.width, self.height, self.boundary).check()?;
+        ensure!(
+            self.contact_radius.is_finite() && self.contact_radius > 0.0,
+            "the contact radius must be positive, not {}",
+            self.contact_radius
+        );
+        self.check_species()?;
+
+        let mut world = World::new(self.width, self.height, self.seed);
+        world.set_boundary(self.boundary);
+        world.set_contact_radius(self.contact_radius);
+        world.set_jitter(self.jitter);
+        world.set_stalemate(self.stalemate);
+        world.set_obstacles(self.obstacles);
+        world.set_line_of_sight(self.line_of_sight);
+        world.set_ruleset(self.ruleset);
+        world.set_fight(self.fight);
+        world.set_mutation(self.mutation);
+        world.set_ecology(self.ecology);
+        for (species, steering) in self.steering {
+            world.set_steering(species, steering);
+        }
+        for (species, behavior) in self.behaviors {
+            let id = world.register_behavior(behavior);
+            world.set_behavior(species, id);
+        }
+
+        let obstacles = world.obstacles.clone();
+        let is_free = |position| !obstacles.iter().any(|o| o.contains(position));
+        let mut spawner = Spawner::new(
+            &self.spawn,
+            self.width,
+            self.height,
+            world.ruleset.len(),
+            &mut world.rng,
+        );
+
+        for (species, count) in self.population {
+            for _ in 0..count {
+                let mut position = spawner.position(&mut world.rng, species, is_free);
+                let mut velocity = world.rng.unit_vector() * world.steering(species).max_speed;
+                // the spawner gives up on crowded arenas, agents it left
+                // inside an obstacle start on its edge instead
+                for obstacle in &obstacles {
+                    obstacle.push_out(&mut position, &mut velocity);
+                }
+                let mut agent = Agent::new(position, velocity, species);
+                if let Some(ecology) = &self.ecology {
+                    agent.energy = ecology.initial_energy;
+                }
+
+                world.spawn(agent);
+            }
+        }
+        world.reindex();
+        if let Some(interval) = self.statistics {
+            world.record_statistics(interval);
+        }
+        if self.events {
+            world.record_events();
+        }
+
+        Ok(world)
+    }
+
+    fn check_species(&self) -> Result<()> {
+        let len = self.ruleset.len();
+        let mask = match &self.spawn {
+            SpawnPattern::Mask(mask) => &mask.colors[..],
+            _ => &[],
+        };
+        let unknown = self
+            .steering
+            .iter()
+            .map(|&(species, _)| species)
+            .chain(self.behaviors.iter().map(|(species, _)| *species))
+            .chain(self.population.iter().map(|&(species, _)| species))
+            .chain(mask.iter().map(|&(_, species)| species))
+            .find(|species| species.index() >= len);
+        if let Some(species) = unknown {
+            bail!("unknown species {} in a ruleset of {len}", species.0);
+        }
+
+        if let Some(odds) = &self.fight.odds {
+            ensure!(
+                odds.len() == len,
+                "the win matrix does not match the ruleset"
+            );
+        }
+        if let Some(matrix) = &self.mutation.matrix {
+            ensure!(
+                matrix.len() == len,
+                "the mutation matrix does not match the ruleset"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for WorldBuilder {
+    fn default() -> Self {
+        Self {
+            width: 640.0,
+            height: 480.0,
+            boundary: Boundary::default(),
+            seed: 0,
+            jitter: 0.0,
+            contact_radius: World::DEFAULT_CONTACT_RADIUS,
+            stalemate: Stalemate::default(),
+            obstacles: Vec::new(),
+            line_of_sight: false,
+            fight: FightModel::default(),
+            mutation: Mutation::default(),
+            ecology: None,
+            statistics: None,
+            events: false,
+            ruleset: Ruleset::default(),
+            steering: Vec::new(),
+            behaviors: Vec::new(),
+            population: Vec::new(),
+            spawn: SpawnPattern::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct World {
+    arena: Arena,
+    contact_radius: f32,
+    jitter: f32,
+    stalemate: Stalemate,
+    obstacles: Vec<Obstacle>,
+    line_of_sight: bool,
+    fight: FightModel,
+    mutation: Mutation,
+    ecology: Option<Ecology>,
+    ruleset: Ruleset,
+    steering: Vec<Steering>,
+    /// Every behaviour added so far, indexed by [`BehaviorId`].
+    behaviors: Vec<Arc<dyn Behavior>>,
+    species_behaviors: Vec<BehaviorId>,
+    seed: u64,
+    rng: Rng,
+    state: SimulationState,
+    tick: u64,
+    elapsed: f64,
+    last_conversion: f64,
+    finish: Option<Finish>,
+    statistics: Option<Statistics>,
+    /// Events not drained yet, `None` while events are not recorded.
+    events: Option<Vec<SimulationEvent>>,
+    /// Agents and generator as they were before the first step, for [`World::reset`].
+    start: Option<(Vec<Agent>, Rng)>,
+    agents: Vec<Agent>,
+    grid: SpatialGrid,
+    contacts: Vec<(usize, usize)>,
+    /// Pairs in contact at the end of the last step, sorted.
+    touching: Vec<(usize, usize)>,
+}
+
+impl World {
+    pub const DEFAULT_CONTACT_RADIUS: f32 = 16.0;
+
+    /// Creates an empty world, every random decision it makes is drawn from
+    /// a generator seeded with `seed`.
+    ///
+    /// Agents bounce off the edges of the arena until
+    /// [`World::set_boundary`] says otherwise.
+    pub fn new(width: f32, height: f32, seed: u64) -> Self {
+        let ruleset = Ruleset::default();
+
+        Self {
+            arena: Arena::new(width, height, Boundary::default()),
+            contact_radius: Self::DEFAULT_CONTACT_RADIUS,
+            jitter: 0.0,
+            stalemate: Stalemate::default(),
+            obstacles: Vec::new(),
+            line_of_sight: false,
+            fight: FightModel::default(),
+            mutation: Mutation::default(),
+            ecology: None,
+            steering: vec![Steering::default(); ruleset.len()],
+            behaviors: vec![Arc::new(ChaseFlee)],
+            species_behaviors: vec![BehaviorId::CHASE_FLEE; ruleset.len()],
+            ruleset,
+            seed,
+            rng: Rng::new(seed),
+            state: SimulationState::Play,
+            tick: 0,
+            elapsed: 0.0,
+            last_conversion: 0.0,
+            finish: None,
+            statistics: None,
+            events: None,
+            start: None,
+            agents: Vec::new(),
+            grid: SpatialGrid::new(width, height, Self::DEFAULT_CONTACT_RADIUS),
+            contacts: Vec::new(),
+            touching: Vec::new(),
+        }
+    }
+
+    /// Panics unless `contact_radius` is finite and positive.
+    pub fn set_contact_radius(&mut self, contact_radius: f32) {
+        self.contact_radius = contact_radius;
+        self.rebuild_grid();
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.arena.boundary = boundary;
+        self.rebuild_grid();
+    }
+
+    fn rebuild_grid(&mut self) {
+        let Arena { width, height, .. } = self.arena;
+        self.grid = match self.arena.boundary {
+            Boundary::Wrap => SpatialGrid::wrapping(width, height, self.contact_radius),
+            _ => SpatialGrid::new(width, height, self.contact_radius),
+        };
+        self.reindex();
+    }
+
+    pub fn set_jitter(&mut self, jitter: f32) {
+        self.jitter = jitter;
+    }
+
+    pub fn set_stalemate(&mut self, stalemate: Stalemate) {
+        self.stalemate = stalemate;
+    }
+
+    pub fn set_obstacles(&mut self, obstacles: Vec<Obstacle>) {
+        self.obstacles = obstacles;
+    }
+
+    pub fn add_obstacle(&mut self, obstacle: Obstacle) {
+        self.obstacles.push(obstacle);
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+
+    /// Whether `position` is inside one of the obstacles.
+    pub fn is_blocked(&self, position: Vec2) -> bool {
+        self.obstacles.iter().any(|o| o.contains(position))
+    }
+
+    /// When set, agents neither chase, flee nor fight agents hidden behind
+    /// an obstacle.
+    pub fn set_line_of_sight(&mut self, line_of_sight: bool) {
+        self.line_of_sight = line_of_sight;
+    }
+
+    pub fn line_of_sight(&self) -> bool {
+        self.line_of_sight
+    }
+
+    /// Panics if the odds of the model are not for the species of the ruleset.
+    pub fn set_fight(&mut self, fight: FightModel) {
+        if let Some(odds) = &fight.odds {
+            assert_eq!(
+                odds.len(),
+                self.ruleset.len(),
+                "the win matrix does not match the ruleset"
+            );
+        }
+        self.fight = fight;
+    }
+
+    pub fn fight(&self) -> &FightModel {
+        &self.fight
+    }
+
+    /// With a non-zero rate the world no longer finishes when a single
+    /// species is left, only the [`Stalemate`] conditions end it.
+    ///
+    /// Panics if the matrix is not for the species of the ruleset.
+    pub fn set_mutation(&mut self, mutation: Mutation) {
+        if let Some(matrix) = &mutation.matrix {
+            assert_eq!(
+                matrix.len(),
+                self.ruleset.len(),
+                "the mutation matrix does not match the ruleset"
+            );
+        }
+        self.mutation = mutation;
+    }
+
+    pub fn mutation(&self) -> &Mutation {
+        &self.mutation
+    }
+
+    /// With an ecology the winners of fights eat the losers, `None`
+    /// converts them.
+    pub fn set_ecology(&mut self, ecology: Option<Ecology>) {
+        self.ecology = ecology;
+    }
+
+    pub fn ecology(&self) -> Option<&Ecology> {
+        self.ecology.as_ref()
+    }
+
+    /// Replaces the ruleset, steering parameters, behaviours, the fight
+    /// model and mutations fall back to their defaults and recorded
+    /// statistics are discarded.
+    pub fn set_ruleset(&mut self, ruleset: Ruleset) {
+        self.steering = vec![Steering::default(); ruleset.len()];
+        self.species_behaviors = vec![BehaviorId::CHASE_FLEE; ruleset.len()];
+        self.fight = FightModel::default();
+        self.mutation = Mutation::default();
+        self.ruleset = ruleset;
+
+        if let Some(interval) = self.statistics.as_ref().map(Statistics::interval) {
+            self.record_statistics(interval);
+        }
+    }
+
+    /// Starts recording [`Statistics`] every `interval` ticks, beginning with
+    /// a sample of the current state.
+    pub fn record_statistics(&mut self, interval: u64) {
+        let mut statistics = Statistics::new(interval, &self.ruleset);
+        statistics.record(self.tick, self.elapsed, &self.agents);
+        self.statistics = Some(statistics);
+    }
+
+    pub fn statistics(&self) -> Option<&Statistics> {
+        self.statistics.as_ref()
+    }
+
+    /// Starts queueing [`SimulationEvent`]s, they pile up until drained.
+    pub fn record_events(&mut self) {
+        self.events.get_or_insert_with(Vec::new);
+    }
+
+    /// Events queued since they were last drained, oldest first.
+    pub fn events(&self) -> &[SimulationEvent] {
+        self.events.as_deref().unwrap_or_default()
+    }
+
+    /// Takes the queued events, oldest first, meant to be called after
+    /// every step.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = SimulationEvent> + '_ {
+        self.events.iter_mut().flat_map(|events| events.drain(..))
+    }
+
+    fn emit(&mut self, event: SimulationEvent) {
+        if let Some(events) = &mut self.events {
+            events.push(event);
+        }
+    }
+
+    pub fn ruleset(&self) -> &Ruleset {
+        &self.ruleset
+    }
+
+    pub fn set_steering<S>(&mut self, species: S, steering: Steering)
+    where
+        S: Into<Species>,
+    {
+        self.steering[species.into().index()] = steering;
+    }
+
+    pub fn steering<S>(&self, species: S) -> &Steering
+    where
+        S: Into<Species>,
+    {
+        &self.steering[species.into().index()]
+    }
+
+    /// Makes `behavior` available to [`World::set_behavior`] and
+    /// [`Agent::behavior`]. Behaviours are code, snapshots and replays do
+    /// not keep them.
+    pub fn add_behavior<B>(&mut self, behavior: B) -> BehaviorId
+    where
+        B: Behavior + 'static,
+    {
+        self.register_behavior(Arc::new(behavior))
+    }
+
+    fn register_behavior(&mut self, behavior: Arc<dyn Behavior>) -> BehaviorId {
+        self.behaviors.push(behavior);
+        BehaviorId(self.behaviors.len() as u32 - 1)
+    }
+
+    /// Panics if `behavior` was not added to this world.
+    pub fn set_behavior<S>(&mut self, species: S, behavior: BehaviorId)
+    where
+        S: Into<Species>,
+    {
+        assert!(behavior.index() < self.behaviors.len(), "unknown behavior");
+        self.species_behaviors[species.into().index()] = behavior;
+    }
+
+    pub fn behavior<S>(&self, species: S) -> BehaviorId
+    where
+        S: Into<Species>,
+    {
+        self.species_behaviors[species.into().index()]
+    }
+
+    /// Gives the agent at `index` its own behaviour, `None` goes back to the
+    /// one of its species. The agent keeps it when it changes species.
+    ///
+    /// Panics if `behavior` was not added to this world.
+    pub fn set_agent_behavior(&mut self, index: usize, behavior: Option<BehaviorId>) {
+        if let Some(id) = behavior {
+            assert!(id.index() < self.behaviors.len(), "unknown behavior");
+        }
+        self.agents[index].behavior = behavior;
+    }
+
+    /// Panics if the species of the agent is not in the ruleset.
+    pub fn spawn(&mut self, agent: Agent) {
+        assert!(
+            agent.species.index() < self.ruleset.len(),
+            "unknown species {}",
+            agent.species.0
+        );
+        self.emit(SimulationEvent::Spawn {
+            tick: self.tick,
+            agent: self.agents.len(),
+            species: agent.species,
+            position: agent.position,
+        });
+        self.agents.push(agent);
+    }
+
+    /// Removes the agent at `index`, the following agents shift down by one.
+    pub fn remove(&mut self, index: usize) -> Agent {
+        let agent = self.agents[index];
+        let kept = (0..self.agents.len())
+            .map(|i| i != index)
+            .collect::<Vec<_>>();
+        self.retain_agents(&kept);
+        agent
+    }
+
+    pub fn agents(&self) -> &[Agent] {
+        &self.agents
+    }
+
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+
+    pub fn size(&self) -> (f32, f32) {
+        (self.arena.width, self.arena.height)
+    }
+
+    pub fn contact_radius(&self) -> f32 {
+        self.contact_radius
+    }
+
+    /// Spatial index of the agent positions as of the last step, agents are
+    /// indexed in the same order as [`World::agents`].
+    pub fn grid(&self) -> &SpatialGrid {
+        &self.grid
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn state(&self) -> SimulationState {
+        self.state
+    }
+
+    /// Number of steps simulated so far.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Simulated time so far, in seconds.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// FNV-1a hash of the tick, the generator and every agent, two worlds
+    /// with the same checksum almost certainly went through the same run.
+    pub fn checksum(&self) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+        let mut write = |bytes: &[u8]| {
+            for &byte in bytes {
+                hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+
+        write(&self.tick.to_le_bytes());
+        for word in self.rng.state() {
+            write(&word.to_le_bytes());
+        }
+        for agent in &self.agents {
+            write(&agent.position.x.to_le_bytes());
+            write(&agent.position.y.to_le_bytes());
+            write(&agent.velocity.x.to_le_bytes());
+            write(&agent.velocity.y.to_le_bytes());
+            write(&[agent.species.0]);
+            write(&agent.health.to_le_bytes());
+            write(&agent.energy.to_le_bytes());
+        }
+
+        hash
+    }
+
+    /// How the simulation ended, once it is in [`SimulationState::Finish`].
+    pub fn finish(&self) -> Option<Finish> {
+        self.finish
+    }
+
+    pub fn population<S>(&self, species: S) -> usize
+    where
+        S: Into<Species>,
+    {
+        let species = species.into();
+        self.agents.iter().filter(|a| a.species == species).count()
+    }
+
+    /// Number of agents of every species, indexed by species.
+    pub fn populations(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.ruleset.len()];
+        for agent in &self.agents {
+            counts[agent.species.index()] += 1;
+        }
+        counts
+    }
+
+    /// The species every agent belongs to, if there is only one left.
+    pub fn winner(&self) -> Option<Species> {
+        let species = self.agents.first()?.species;
+        self.agents
+            .iter()
+            .all(|a| a.species == species)
+            .then_some(species)
+    }
+
+    /// Steers and advances every agent by `dt` seconds, then resolves the
+    /// fights between the agents that ended up in contact and mutates some.
+    ///
+    /// Does nothing unless the world is in [`SimulationState::Play`], which
+    /// it leaves for [`SimulationState::Finish`] once at most one species
+    /// remains or the [`Stalemate`] conditions hold. Returns the finish on
+    /// the step that caused it.
+    pub fn step(&mut self, dt: f32) -> Option<Finish> {
+        if self.state != SimulationState::Play {
+            return None;
+        }
+
+        self.advance(dt)
+    }
+
+    pub fn pause(&mut self) {
+        if self.state == SimulationState::Play {
+            self.state = SimulationState::Pause;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.state == SimulationState::Pause {
+            self.state = SimulationState::Play;
+        }
+    }
+
+    /// Advances a paused world by a single step, leaving it paused.
+    pub fn step_once(&mut self, dt: f32) -> Option<Finish> {
+        if self.state != SimulationState::Pause {
+            return None;
+        }
+
+        let finish = self.advance(dt);
+        if finish.is_none() {
+            self.state = SimulationState::Pause;
+        }
+        finish
+    }
+
+    /// Puts the agents back where they were before the first step and
+    /// starts playing again, the run then repeats identically.
+    pub fn reset(&mut self) {
+        if let Some((agents, rng)) = self.start.take() {
+            self.agents = agents;
+            self.rng = rng;
+        }
+
+        self.state = SimulationState::Play;
+        self.tick = 0;
+        self.elapsed = 0.0;
+        self.last_conversion = 0.0;
+        self.finish = None;
+        self.touching.clear();
+        self.reindex();
+
+        if let Some(statistics) = &mut self.statistics {
+            statistics.clear();
+            statistics.record(0, 0.0, &self.agents);
+        }
+    }
+
+    fn advance(&mut self, dt: f32) -> Option<Finish> {
+        if self.start.is_none() {
+            self.start = Some((self.agents.clone(), self.rng.clone()));
+        }
+
+        let populations = self.events.is_some().then(|| self.populations());
+        self.reindex();
+        self.steer(dt);
+        self.integrate(dt);
+        self.reindex();
+        if let Some(combat) = self.fight.combat {
+            self.recover(&combat, dt);
+        }
+        let conversions = self.resolve_contacts(dt);
+        let mutations = self.mutate();
+        if let Some(ecology) = self.ecology {
+            self.live(&ecology, dt);
+        }
+        self.tick += 1;
+        self.elapsed += dt as f64;
+        if conversions > 0 {
+            self.last_conversion = self.elapsed;
+        }
+
+        if let Some(populations) = populations {
+            self.emit_extinctions(&populations);
+        }
+
+        self.state = SimulationState::Play;
+        let finish = self.check_finish();
+        if let Some(finish) = finish {
+            self.emit(SimulationEvent::Finish {
+                tick: self.tick,
+                finish,
+            });
+        }
+
+        if let Some(statistics) = &mut self.statistics {
+            statistics.count_conversions(conversions);
+            statistics.count_mutations(mutations);
+            if self.tick.is_multiple_of(statistics.interval()) || finish.is_some() {
+                statistics.record(self.tick, self.elapsed, &self.agents);
+            }
+        }
+
+        finish
+    }
+
+    fn check_finish(&mut self) -> Option<Finish> {
+        let Stalemate {
+            time_limit,
+            quiet_period,
+        } = self.stalemate;
+        let remaining = self.populations().iter().filter(|&&n| n > 0).count();
+
+        // mutations can bring back extinct species
+        let finished = remaining == 0
+            || remaining == 1 && !self.mutation.is_enabled()
+            || time_limit.is_some_and(|limit| self.elapsed >= limit)
+            || quiet_period.is_some_and(|period| self.elapsed - self.last_conversion >= period);
+        if !finished {
+            return None;
+        }
+
+        let finish = Finish {
+            winner: self.winner(),
+            elapsed: self.elapsed,
+        };
+        self.state = SimulationState::Finish;
+        self.finish = Some(finish);
+        Some(finish)
+    }
+
+    /// Reports the species that had agents at the start of the step and no
+    /// longer do.
+    fn emit_extinctions(&mut self, before: &[usize]) {
+        let after = self.populations();
+        for species in self.ruleset.species() {
+            if before[species.index()] > 0 && after[species.index()] == 0 {
+                self.emit(SimulationEvent::Extinction {
+                    tick: self.tick,
+                    species,
+                });
+            }
+        }
+    }
+
+    /// Rebuilds the spatial index from the current agent positions.
+    pub fn reindex(&mut self) {
+        self.grid.rebuild(self.agents.iter().map(|a| a.position));
+    }
+
+    /// Whether an obstacle stands between `a` and `b`, never when the world
+    /// does not check the line of sight.
+    fn is_hidden(&self, a: Vec2, b: Vec2) -> bool {
+        self.line_of_sight && self.obstacles.iter().any(|o| o.blocks(a, b))
+    }
+
+    fn steer(&mut self, dt: f32) {
+        let mut velocities = Vec::with_capacity(self.agents.len());
+        let mut neighbours = Vec::new();
+        for (index, agent) in self.agents.iter().enumerate() {
+            let steering = &self.steering[agent.species.index()];
+
+            neighbours.clear();
+            self.grid
+                .for_each_within(agent.position, steering.sensing_radius, |j, other| {
+                    if j == index || self.is_hidden(agent.position, other) {
+                        return;
+                    }
+
+                    // the grid already hands out the nearest wrapped copy
+                    let neighbour = &self.agents[j];
+                    neighbours.push(Neighbour {
+                        index: j,
+                        offset: other - agent.position,
+                        velocity: neighbour.velocity,
+                        species: neighbour.species,
+                        result: self.ruleset.fight(agent.species, neighbour.species),
+                    });
+                });
+
+            let obstacle = self
+                .obstacles
+                .iter()
+                .map(|o| o.closest_point(agent.position) - agent.position)
+                .filter(|offset| offset.length() < steering.sensing_radius)
+                .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+
+            let view = Neighbourhood {
+                index,
+                agent,
+                steering,
+                ruleset: &self.ruleset,
+                neighbours: &neighbours,
+                obstacle,
+                dt,
+            };
+            // agents may carry the behaviour of another world
+            let behavior = agent
+                .behavior
+                .and_then(|id| self.behaviors.get(id.index()))
+                .unwrap_or(&self.behaviors[self.species_behaviors[agent.species.index()].index()]);
+            velocities.push(behavior.steer(&view, &mut self.rng));
+        }
+
+        for (agent, velocity) in self.agents.iter_mut().zip(velocities) {
+            agent.velocity = velocity;
+        }
+    }
+
+    /// Keeps the agents whose entry of `kept` is `true`, the contacts of the
+    /// last step and the events of this one follow their agents to their new
+    /// index.
+    fn retain_agents(&mut self, kept: &[bool]) {
+        let mut indices = Vec::with_capacity(kept.len());
+        let mut count = 0;
+        for &keep in kept {
+            indices.push(keep.then_some(count));
+            count += keep as usize;
+        }
+        self.touching = self
+            .touching
+            .iter()
+            .filter_map(|&(i, j)| Some((indices[i]?, indices[j]?)))
+            .collect();
+
+        // so do the mutations of the ongoing step, agents that mutated and
+        // then starved only report their death
+        let tick = self.tick + 1;
+        if let Some(events) = &mut self.events {
+            events.retain_mut(|event| match event {
+                SimulationEvent::Mutation { tick: t, agent, .. } if *t == tick => {
+                    match indices[*agent] {
+                        Some(index) => {
+                            *agent = index;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                _ => true,
+            });
+        }
+
+        let mut kept = kept.iter();
+        self.agents.retain(|_| *kept.next().unwrap());
+    }
+
+    /// Moves the agents, applies the arena boundary and pushes agents back
+    /// out of the obstacles, absorbed agents are removed from the world.
+    fn integrate(&mut self, dt: f32) {
+        for agent in &mut self.agents {
+            if self.jitter > 0.0 {
+                let speed = agent.velocity.length();
+                let nudge = self.rng.unit_vector() * (self.jitter * dt);
+                agent.velocity = (agent.velocity + nudge).normalize_or_zero() * speed;
+            }
+
+            agent.position += agent.velocity * dt;
+        }
+
+        let arena = self.arena;
+        let tick = self.tick + 1;
+        let events = &mut self.events;
+        let inside = self
+            .agents
+            .iter_mut()
+            .map(|agent| {
+                let inside = arena.confine(&mut agent.position, &mut agent.velocity);
+                if let (false, Some(events)) = (inside, events.as_mut()) {
+                    events.push(SimulationEvent::Death {
+                        tick,
+                        species: agent.species,
+                        position: agent.position,
+                        cause: DeathCause::Absorbed,
+                    });
+                }
+                inside
+            })
+            .collect::<Vec<_>>();
+        self.retain_agents(&inside);
+
+        for agent in &mut self.agents {
+            for obstacle in &self.obstacles {
+                obstacle.push_out(&mut agent.position, &mut agent.velocity);
+            }
+        }
+    }
+
+    /// Returns the number of agents that changed species, or were eaten in
+    /// an [`Ecology`].
+    fn resolve_contacts(&mut self, dt: f32) -> usize {
+        let mut conversions = 0;
+        let mut contacts = self.grid.pairs_within(self.contact_radius);
+        contacts.retain(|&(i, j)| {
+            let a = self.agents[i].position;
+            !self.is_hidden(a, a + self.arena.displacement(a, self.agents[j].position))
+        });
+        self.contacts = contacts;
+        let mut touching = self.contacts.clone();
+        touching.sort_unstable();
+
+        // agents touching several others at once are resolved in a random,
+        // but seeded, order so no index gets an advantage
+        self.rng.shuffle(&mut self.contacts);
+
+        let contacts = std::mem::take(&mut self.contacts);
+        for &(i, j) in &contacts {
+            let (a, b) = (self.agents[i].species, self.agents[j].species);
+            if self.ecology.is_some() && (self.is_dead(i) || self.is_dead(j)) {
+                continue;
+            }
+
+            let result = if self.fight.is_tie(&self.ruleset, a, b) {
+                match self.fight.ties {
+                    TieBreak::Nothing => continue,
+                    TieBreak::PushApart => {
+                        self.push_apart(i, j);
+                        continue;
+                    }
+                    TieBreak::RandomConversion if a == b => continue,
+                    TieBreak::RandomConversion if self.rng.chance(0.5) => FightResults::Won,
+                    TieBreak::RandomConversion => FightResults::Lost,
+                }
+            } else if let Some(combat) = self.fight.combat {
+                match self.exchange_hits(i, j, &combat) {
+                    FightResults::Tied => continue,
+                    result => result,
+                }
+            } else if let Some(odds) = &self.fight.odds {
+                let ongoing = self.touching.binary_search(&(i, j)).is_ok();
+                if odds.timing == FightTiming::PerContact && ongoing {
+                    continue;
+                }
+
+                let (p, q) = odds.chances(a, b, dt);
+                let roll = self.rng.next_f32() * (p + q).max(1.0);
+                if roll < p {
+                    FightResults::Won
+                } else if roll < p + q {
+                    FightResults::Lost
+                } else {
+                    continue;
+                }
+            } else {
+                self.ruleset.fight(a, b)
+            };
+
+            let (winner, loser) = match result {
+                FightResults::Won => (i, j),
+                FightResults::Lost => (j, i),
+                FightResults::Tied => continue,
+            };
+            let species = self.agents[winner].species;
+            let (from, position) = (self.agents[loser].species, self.agents[loser].position);
+            if let Some(ecology) = &self.ecology {
+                // eaten agents are removed along with the starved ones
+                self.agents[winner].energy += ecology.prey_energy;
+                self.agents[loser].energy = 0.0;
+                self.emit(SimulationEvent::Death {
+                    tick: self.tick + 1,
+                    species: from,
+                    position,
+                    cause: DeathCause::Eaten { predator: species },
+                });
+            } else {
+                let converted = &mut self.agents[loser];
+                converted.species = species;
+                converted.health = 1.0;
+                self.emit(SimulationEvent::Conversion {
+                    tick: self.tick + 1,
+                    attacker: winner,
+                    victim: loser,
+                    species,
+                    from,
+                    position,
+                });
+            }
+            conversions += 1;
+        }
+        self.contacts = contacts;
+        self.touching = touching;
+
+        conversions
+    }
+
+    fn is_dead(&self, index: usize) -> bool {
+        self.agents[index].energy <= 0.0
+    }
+
+    /// Burns energy, removes the agents left without any and splits those
+    /// with enough of it while the carrying capacity allows.
+    fn live(&mut self, ecology: &Ecology, dt: f32) {
+        let tick = self.tick + 1;
+        for agent in &mut self.agents {
+            // eaten agents already have no energy left
+            let starving = agent.energy > 0.0;
+            agent.energy -= ecology.metabolism * dt;
+            if let (true, Some(events)) = (starving && agent.energy <= 0.0, &mut self.events) {
+                events.push(SimulationEvent::Death {
+                    tick,
+                    species: agent.species,
+                    position: agent.position,
+                    cause: DeathCause::Starved,
+                });
+            }
+        }
+
+        let alive = self
+            .agents
+            .iter()
+            .map(|agent| agent.energy > 0.0)
+            .collect::<Vec<_>>();
+        self.retain_agents(&alive);
+
+        for i in 0..self.agents.len() {
+            if self.agents.len() >= ecology.carrying_capacity {
+                break;
+            }
+            if self.agents[i].energy < ecology.reproduction_threshold {
+                continue;
+            }
+
+            self.agents[i].energy /= 2.0;
+            let parent = self.agents[i];
+            let speed = self.steering[parent.species.index()].max_speed;
+            self.emit(SimulationEvent::Spawn {
+                tick,
+                agent: self.agents.len(),
+                species: parent.species,
+                position: parent.position,
+            });
+            self.agents.push(Agent {
+                velocity: self.rng.unit_vector() * speed,
+                health: 1.0,
+                cooldown: 0.0,
+                ..parent
+            });
+        }
+
+        self.reindex();
+    }
+
+    /// Returns the number of agents that mutated.
+    fn mutate(&mut self) -> usize {
+        if !self.mutation.is_enabled() {
+            return 0;
+        }
+
+        let mut mutations = 0;
+        let len = self.ruleset.len();
+        for (index, agent) in self.agents.iter_mut().enumerate() {
+            // agents eaten this step are about to be removed
+            if self.ecology.is_some() && agent.energy <= 0.0 {
+                continue;
+            }
+            if !self.rng.chance(self.mutation.rate) {
+                continue;
+            }
+            if let Some(species) = self.mutation.pick(&mut self.rng, agent.species, len) {
+                if let Some(events) = &mut self.events {
+                    events.push(SimulationEvent::Mutation {
+                        tick: self.tick + 1,
+                        agent: index,
+                        from: agent.species,
+                        to: species,
+                        position: agent.position,
+                    });
+                }
+                agent.species = species;
+                agent.health = 1.0;
+                mutations += 1;
+            }
+        }
+
+        mutations
+    }
+
+    /// Heals the agents and counts their cooldowns down.
+    fn recover(&mut self, combat: &Combat, dt: f32) {
+        for agent in &mut self.agents {
+            agent.health = (agent.health + combat.healing * dt).min(1.0);
+            agent.cooldown = (agent.cooldown - dt).max(0.0);
+        }
+    }
+
+    /// Lets both agents hit the other if their cooldown allows, returns
+    /// `Won` if the first one takes the second down, `Lost` for the reverse
+    /// and `Tied` while both stand.
+    fn exchange_hits(&mut self, i: usize, j: usize, combat: &Combat) -> FightResults {
+        // hits land at the same time, damage is computed before either applies
+        let damage = [(i, j), (j, i)].map(|(attacker, victim)| {
+            let (attacker, victim) = (&self.agents[attacker], &self.agents[victim]);
+            if attacker.cooldown > 0.0 {
+                return 0.0;
+            }
+            combat.damage(self.ruleset.fight(attacker.species, victim.species))
+        });
+        for (index, damage) in [(j, damage[0]), (i, damage[1])] {
+            if damage > 0.0 {
+                self.agents[index].health -= damage;
+            }
+        }
+        for (attacker, damage) in [(i, damage[0]), (j, damage[1])] {
+            if damage > 0.0 {
+                self.agents[attacker].cooldown = combat.cooldown;
+            }
+        }
+
+        let (first, second) = (self.agents[i].health, self.agents[j].health);
+        match (first <= 0.0, second <= 0.0) {
+            (false, false) => FightResults::Tied,
+            (false, true) => FightResults::Won,
+            (true, false) => FightResults::Lost,
+            // both fall, the one left with more health wins
+            (true, true) if first > second => FightResults::Won,
+            (true, true) if first < second => FightResults::Lost,
+            (true, true) if self.rng.chance(0.5) => FightResults::Won,
+            (true, true) => FightResults::Lost,
+        }
+    }
+
+    /// Moves two touching agents apart along the line joining them until
+    /// they are just out of contact.
+    fn push_apart(&mut self, i: usize, j: usize) {
+        let offset = self
+            .arena
+            .displacement(self.agents[i].position, self.agents[j].position);
+        let distance = offset.length();
+        let normal = if distance > 0.0 {
+            offset * (1.0 / distance)
+        } else {
+            self.rng.unit_vector()
+        };
+
+        // a little further than needed, so they no longer count as touching
+        let push = normal * (((self.contact_radius - distance).max(0.0) + 0.01) / 2.0);
+        let Arena { width, height, .. } = self.arena;
+        for (index, push) in [(i, -push), (j, push)] {
+            let agent = &mut self.agents[index];
+            agent.position += push;
+            if self.arena.boundary == Boundary::Wrap {
+                let mut velocity = agent.velocity;
+                self.arena.confine(&mut agent.position, &mut velocity);
+            } else {
+                agent.position.x = agent.position.x.clamp(0.0, width);
+                agent.position.y = agent.position.y.clamp(0.0, height);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventBus, Shape, Wander, WinMatrix};
+
+    #[test]
+    fn winner_converts_loser_on_contact() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([55.0, 50.0], [0.0, 0.0], Shape::Scissors));
+        world.spawn(Agent::new([5.0, 5.0], [0.0, 0.0], Shape::Paper));
+
+        world.step(0.1);
+
+        assert_eq!(world.population(Shape::Rock), 2);
+        assert_eq!(world.population(Shape::Scissors), 0);
+        assert_eq!(world.population(Shape::Paper), 1);
+    }
+
+    #[test]
+    fn finishes_when_one_shape_is_left() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Paper));
+        world.spawn(Agent::new([55.0, 50.0], [0.0, 0.0], Shape::Rock));
+
+        let finish = world.step(0.1);
+        world.step(0.1);
+
+        assert_eq!(world.state(), SimulationState::Finish);
+        assert_eq!(world.winner(), Some(Shape::Paper.into()));
+        assert_eq!(finish.unwrap().winner, Some(Shape::Paper.into()));
+        assert_eq!(world.tick(), 1);
+    }
+
+    #[test]
+    fn builder_rejects_unknown_species() {
+        let builder = WorldBuilder::default().population(Species(3), 10);
+        assert!(builder.build().is_err());
+
+        let builder = WorldBuilder::default()
+            .ruleset(Ruleset::rpsls())
+            .population(Species(3), 10);
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn builder_rejects_empty_arenas() {
+        for (width, height) in [(0.0, 100.0), (100.0, -1.0), (f32::NAN, 100.0)] {
+            let builder = WorldBuilder::default().size(width, height);
+            assert!(builder.build().is_err());
+        }
+    }
+
+    #[test]
+    fn stalemates_finish_without_a_winner() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.set_stalemate(Stalemate {
+            time_limit: None,
+            quiet_period: Some(0.5),
+        });
+        world.spawn(Agent::new([10.0, 10.0], [0.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([90.0, 90.0], [0.0, 0.0], Shape::Paper));
+
+        let finish = (0..10).find_map(|_| world.step(0.1)).unwrap();
+
+        assert_eq!(finish.winner, None);
+        assert!((finish.elapsed - 0.5).abs() < 1e-6);
+        assert_eq!(world.state(), SimulationState::Finish);
+    }
+
+    #[test]
+    fn pause_step_once_and_reset() {
+        let mut world = WorldBuilder::new()
+            .seed(5)
+            .jitter(30.0)
+            .population(Shape::Rock, 10)
+            .population(Shape::Paper, 10)
+            .build()
+            .unwrap();
+        world.step(0.1);
+        let after_one_step = world.agents().to_vec();
+
+        world.pause();
+        world.step(0.1);
+        assert_eq!(world.tick(), 1);
+
+        world.step_once(0.1);
+        assert_eq!(world.tick(), 2);
+        assert_eq!(world.state(), SimulationState::Pause);
+
+        world.reset();
+        world.step(0.1);
+        assert_eq!(world.agents(), after_one_step.as_slice());
+        assert_eq!(world.state(), SimulationState::Play);
+    }
+
+    #[test]
+    fn agents_chase_prey_and_flee_predators() {
+        let mut world = World::new(200.0, 200.0, 0);
+        world.spawn(Agent::new([100.0, 100.0], [10.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([100.0, 150.0], [0.0, 0.0], Shape::Scissors));
+        world.spawn(Agent::new([100.0, 40.0], [-10.0, 0.0], Shape::Paper));
+
+        world.step(0.1);
+
+        // the rock turns toward the scissors and away from the paper, which
+        // in turn chases the rock
+        let agents = world.agents();
+        assert!(agents[0].velocity.y > 0.0);
+        assert!(agents[2].velocity.y > 0.0);
+    }
+
+    #[test]
+    fn behaviors_can_be_set_per_species_or_per_agent() {
+        /// Runs straight away from every neighbour it sees.
+        #[derive(Debug)]
+        struct Shy;
+
+        impl Behavior for Shy {
+            fn steer(&self, view: &Neighbourhood, _rng: &mut Rng) -> Vec2 {
+                let away = view
+                    .neighbours
+                    .iter()
+                    .fold(Vec2::ZERO, |away, n| away - n.offset);
+                away.normalize_or_zero() * view.steering.max_speed
+            }
+        }
+
+        let mut world = WorldBuilder::new()
+            .size(200.0, 200.0)
+            .behavior(Shape::Paper, Shy)
+            .build()
+            .unwrap();
+        let wander = world.add_behavior(Wander::default());
+        world.spawn(Agent::new([100.0, 100.0], [10.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([100.0, 150.0], [0.0, 0.0], Shape::Scissors));
+        world.spawn(Agent::new([100.0, 40.0], [-10.0, 0.0], Shape::Paper));
+        world.spawn(Agent {
+            behavior: Some(BehaviorId::CHASE_FLEE),
+            ..Agent::new([140.0, 40.0], [-10.0, 0.0], Shape::Paper)
+        });
+        world.set_agent_behavior(1, Some(wander));
+        world.reindex();
+
+        world.step(0.1);
+
+        // the first paper runs from the rock instead of chasing it, unlike
+        // the one keeping the default behaviour, and the wandering scissors
+        // set off although they stood still
+        let agents = world.agents();
+        assert_ne!(world.behavior(Shape::Paper), BehaviorId::CHASE_FLEE);
+        assert!(agents[0].velocity.y > 0.0);
+        assert!(agents[2].velocity.y < 0.0);
+        assert!(agents[3].velocity.y > 0.0);
+        assert!((agents[1].velocity.length() - 60.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn agents_bounce_off_the_arena_edges() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.spawn(Agent::new([98.0, 2.0], [40.0, -30.0], Shape::Rock));
+
+        world.step(0.1);
+
+        let agent = world.agents()[0];
+        assert_eq!(agent.position, Vec2::new(98.0, 1.0));
+        assert_eq!(agent.velocity, Vec2::new(-40.0, 30.0));
+    }
+
+    #[test]
+    fn agents_wrap_around_or_get_absorbed() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.set_boundary(Boundary::Wrap);
+        world.spawn(Agent::new([98.0, 50.0], [40.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([4.0, 50.0], [0.0, 0.0], Shape::Scissors));
+
+        world.step(0.1);
+
+        // the rock crossed the edge and met the scissors on the other side
+        assert_eq!(world.agents()[0].position, Vec2::new(2.0, 50.0));
+        assert_eq!(world.population(Shape::Rock), 2);
+
+        let mut world = World::new(100.0, 100.0, 0);
+        world.set_boundary(Boundary::Absorb);
+        world.spawn(Agent::new([98.0, 50.0], [40.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Rock));
+
+        world.step(0.1);
+
+        assert_eq!(world.agents().len(), 1);
+    }
+
+    #[test]
+    fn events_report_what_happened_in_the_step() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.set_boundary(Boundary::Absorb);
+        world.record_events();
+        world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([55.0, 50.0], [0.0, 0.0], Shape::Scissors));
+        world.spawn(Agent::new([98.0, 50.0], [40.0, 0.0], Shape::Paper));
+
+        let received = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut bus = EventBus::new();
+        bus.subscribe({
+            let received = received.clone();
+            move |event: &SimulationEvent| received.borrow_mut().push(*event)
+        });
+
+        assert_eq!(bus.dispatch(&mut world), 3);
+        let finish = world.step(0.1).unwrap();
+        assert_eq!(bus.dispatch(&mut world), 5);
+        assert!(world.events().is_empty());
+
+        let (rock, paper, scissors) = (
+            Shape::Rock.into(),
+            Shape::Paper.into(),
+            Shape::Scissors.into(),
+        );
+        let received = received.borrow();
+        assert_eq!(
+            received[3..],
+            [
+                SimulationEvent::Death {
+                    tick: 1,
+                    species: paper,
+                    position: Vec2::new(102.0, 50.0),
+                    cause: DeathCause::Absorbed,
+                },
+                SimulationEvent::Conversion {
+                    tick: 1,
+                    attacker: 0,
+                    victim: 1,
+                    species: rock,
+                    from: scissors,
+                    position: Vec2::new(55.0, 50.0),
+                },
+                SimulationEvent::Extinction {
+                    tick: 1,
+                    species: paper,
+                },
+                SimulationEvent::Extinction {
+                    tick: 1,
+                    species: scissors,
+                },
+                SimulationEvent::Finish { tick: 1, finish },
+            ]
+        );
+    }
+
+    #[test]
+    fn obstacles_block_movement_and_sight() {
+        let mut world = World::new(200.0, 100.0, 0);
+        world.add_obstacle(Obstacle::rect(100.0, 0.0, 10.0, 100.0));
+        world.spawn(Agent::new([98.0, 50.0], [40.0, 0.0], Shape::Rock));
+
+        world.step(0.1);
+
+        let agent = world.agents()[0];
+        assert_eq!(agent.position.x, 100.0);
+        assert!(agent.velocity.x < 0.0);
+
+        // the scissors on the other side of the wall go unnoticed
+        let mut world = World::new(200.0, 100.0, 0);
+        world.add_obstacle(Obstacle::rect(100.0, 0.0, 10.0, 100.0));
+        world.set_steering(
+            Shape::Rock,
+            Steering {
+                avoid_weight: 0.0,
+                ..Default::default()
+            },
+        );
+        world.spawn(Agent::new([80.0, 50.0], [0.0, 10.0], Shape::Rock));
+        world.spawn(Agent::new([130.0, 50.0], [0.0, 0.0], Shape::Scissors));
+
+        let mut blind = world.clone();
+        blind.set_line_of_sight(true);
+        world.step(0.1);
+        blind.step(0.1);
+
+        assert!(world.agents()[0].velocity.x > 0.0);
+        assert_eq!(blind.agents()[0].velocity.x, 0.0);
+
+        let world = WorldBuilder::new()
+            .obstacle(Obstacle::circle(320.0, 240.0, 200.0))
+            .population(Shape::Rock, 50)
+            .build()
+            .unwrap();
+        assert!(world.agents().iter().all(|a| !world.is_blocked(a.position)));
+
+        // nor can they fight through it
+        let mut world = World::new(200.0, 100.0, 0);
+        world.add_obstacle(Obstacle::rect(100.0, 0.0, 2.0, 100.0));
+        world.spawn(Agent::new([95.0, 50.0], [0.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([107.0, 50.0], [0.0, 0.0], Shape::Scissors));
+
+        let mut blind = world.clone();
+        blind.set_line_of_sight(true);
+        world.step(0.1);
+        blind.step(0.1);
+
+        assert_eq!(world.population(Shape::Scissors), 0);
+        assert_eq!(blind.population(Shape::Scissors), 1);
+
+        // agents the spawner cannot fit anywhere start on the edge
+        let world = WorldBuilder::new()
+            .size(100.0, 100.0)
+            .obstacle(Obstacle::rect(0.0, 0.0, 100.0, 100.0))
+            .population(Shape::Rock, 20)
+            .build()
+            .unwrap();
+        assert!(world.agents().iter().all(|a| !world.is_blocked(a.position)));
+    }
+
+    #[test]
+    fn probabilistic_fights_roll_once_per_contact() {
+        let ruleset = Ruleset::classic();
+        let rolls = |timing| {
+            let mut world = World::new(100.0, 100.0, 3);
+            let odds = WinMatrix::from_ruleset(&ruleset, 0.01, timing);
+            world.set_fight(FightModel::probabilistic(odds));
+            world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Rock));
+            world.spawn(Agent::new([55.0, 50.0], [0.0, 0.0], Shape::Scissors));
+
+            let mut rng = world.rng.clone();
+            world.step(0.1);
+            world.step(0.1);
+
+            // every roll draws a single number
+            let mut draws = 0;
+            while rng != world.rng {
+                rng.next_u64();
+                draws += 1;
+            }
+            draws
+        };
+
+        // a contact carrying on only draws once, unless the chances are per second
+        assert_eq!(rolls(FightTiming::PerContact), 1);
+        assert_eq!(rolls(FightTiming::PerSecond), 2);
+    }
+
+    #[test]
+    fn contacts_carry_on_when_other_agents_leave() {
+        let ruleset = Ruleset::classic();
+        let rolls = |absorb: bool| {
+            let mut world = World::new(100.0, 100.0, 3);
+            world.set_boundary(Boundary::Absorb);
+            let odds = WinMatrix::from_ruleset(&ruleset, 0.01, FightTiming::PerContact);
+            world.set_fight(FightModel::probabilistic(odds));
+            world.set_steering(
+                Shape::Paper,
+                Steering {
+                    max_turn_rate: 0.0,
+                    ..Default::default()
+                },
+            );
+            // the paper leaves through the edge during the second step
+            world.spawn(Agent::new([2.0, 95.0], [-15.0, 0.0], Shape::Paper));
+            world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Rock));
+            world.spawn(Agent::new([55.0, 50.0], [0.0, 0.0], Shape::Scissors));
+
+            let mut rng = world.rng.clone();
+            world.step(0.1);
+            if !absorb {
+                world.remove(0);
+            }
+            world.step(0.1);
+            assert_eq!(world.agents().len(), 2);
+
+            let mut draws = 0;
+            while rng != world.rng {
+                rng.next_u64();
+                draws += 1;
+            }
+            draws
+        };
+
+        // the rock and the scissors shift down one index, their contact is
+        // still the same one
+        assert_eq!(rolls(true), 1);
+        assert_eq!(rolls(false), 1);
+    }
+
+    #[test]
+    fn tied_agents_can_be_pushed_apart() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.set_fight(FightModel::deterministic().ties(TieBreak::PushApart));
+        world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([56.0, 50.0], [0.0, 0.0], Shape::Rock));
+
+        world.step(0.1);
+
+        let distance = (world.agents()[1].position - world.agents()[0].position).length();
+        assert!(distance > world.contact_radius());
+        assert_eq!(world.agents()[0].position.y, 50.0);
+    }
+
+    #[test]
+    fn combat_converts_after_several_hits() {
+        let mut world = World::new(100.0, 100.0, 0);
+        let combat = Combat {
+            damage_won: 0.375,
+            damage_lost: 0.125,
+            cooldown: 0.5,
+            healing: 0.25,
+            ..Combat::default()
+        };
+        world.set_fight(FightModel::deterministic().combat(combat));
+        for shape in [Shape::Rock, Shape::Paper] {
+            let steering = Steering {
+                max_speed: 0.0,
+                ..*world.steering(shape)
+            };
+            world.set_steering(shape, steering);
+        }
+        world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Rock));
+        world.spawn(Agent::new([54.0, 50.0], [0.0, 0.0], Shape::Paper));
+
+        // paper hits every other step and rock heals in between
+        let mut health = Vec::new();
+        for _ in 0..6 {
+            world.step(0.25);
+            assert_eq!(world.agents()[0].species, Shape::Rock.into());
+            health.push(world.agents()[0].health);
+        }
+        assert_eq!(health, [0.625, 0.6875, 0.375, 0.4375, 0.125, 0.1875]);
+
+        world.step(0.25);
+        assert_eq!(world.agents()[0].species, Shape::Paper.into());
+        assert_eq!(world.agents()[0].health, 1.0);
+    }
+
+    #[test]
+    fn mutations_revive_extinct_species() {
+        let mut world = WorldBuilder::new()
+            .seed(2)
+            .statistics(10)
+            .stalemate(Stalemate {
+                time_limit: Some(5.0),
+                quiet_period: None,
+            })
+            .mutation(Mutation::new(0.01))
+            .population(Shape::Rock, 30)
+            .build()
+            .unwrap();
+
+        let mut finish = None;
+        while finish.is_none() {
+            finish = world.step(0.1);
+        }
+
+        assert!(finish.unwrap().elapsed >= 5.0);
+        let samples = world.statistics().unwrap().samples();
+        assert!(samples.iter().map(|s| s.mutations).sum::<u64>() > 0);
+        assert!(samples.iter().any(|s| s.populations[0] < 30));
+    }
+
+    #[test]
+    fn predators_eat_breed_and_starve() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.set_ecology(Some(Ecology {
+            prey_energy: 1.5,
+            metabolism: 0.5,
+            reproduction_threshold: 2.0,
+            carrying_capacity: 3,
+            ..Ecology::default()
+        }));
+        for shape in [Shape::Rock, Shape::Paper, Shape::Scissors] {
+            let steering = Steering {
+                max_speed: 0.0,
+                ..*world.steering(shape)
+            };
+            world.set_steering(shape, steering);
+        }
+        world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Paper));
+        world.spawn(Agent::new([54.0, 50.0], [0.0, 0.0], Shape::Rock));
+        world.spawn(Agent {
+            energy: 0.25,
+            ..Agent::new([10.0, 10.0], [0.0, 0.0], Shape::Scissors)
+        });
+
+        // the paper eats the rock and splits the energy it is left with
+        world.step(0.25);
+        assert_eq!(world.populations(), [0, 2, 1]);
+        assert_eq!(world.agents()[0].energy, 1.1875);
+        assert_eq!(world.agents()[2].energy, 1.1875);
+
+        let finish = world.step(0.25);
+        assert_eq!(world.populations(), [0, 2, 0]);
+        assert_eq!(finish.unwrap().winner, Some(Shape::Paper.into()));
+    }
+
+    #[test]
+    fn mutation_events_point_at_agents_after_the_step() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.set_ecology(Some(Ecology::default()));
+        world.set_mutation(Mutation::new(1.0));
+        world.record_events();
+        world.spawn(Agent {
+            energy: 0.01,
+            ..Agent::new([10.0, 10.0], [0.0, 0.0], Shape::Scissors)
+        });
+        world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Paper));
+        world.spawn(Agent::new([54.0, 50.0], [0.0, 0.0], Shape::Rock));
+
+        // the scissors mutate and starve, the paper mutates and eats the rock
+        world.step(0.5);
+
+        let mutations = world
+            .drain_events()
+            .filter_map(|event| match event {
+                SimulationEvent::Mutation { agent, to, .. } => Some((agent, to)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(world.agents().len(), 1);
+        assert_eq!(mutations, [(0, world.agents()[0].species)]);
+    }
+
+    #[test]
+    fn eaten_agents_do_not_mutate() {
+        let mut world = World::new(100.0, 100.0, 0);
+        world.set_ecology(Some(Ecology::default()));
+        world.set_mutation(Mutation::new(1.0));
+        world.record_events();
+        world.spawn(Agent::new([50.0, 50.0], [0.0, 0.0], Shape::Paper));
+        world.spawn(Agent::new([54.0, 50.0], [0.0, 0.0], Shape::Rock));
+
+        world.step(0.1);
+
+        let mutants = world
+            .drain_events()
+            .filter_map(|event| match event {
+                SimulationEvent::Mutation { agent, .. } => Some(agent),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(mutants, [0]);
+        assert_eq!(world.agents().len(), 1);
+    }
+
+    #[test]
+    fn same_seed_reproduces_the_run() {
+        let run = |seed| {
+            let mut world = WorldBuilder::new()
+                .seed(seed)
+                .jitter(30.0)
+                .population(Shape::Rock, 20)
+                .population(Shape::Paper, 20)
+                .population(Shape::Scissors, 20)
+                .build()
+                .unwrap();
+
+            for _ in 0..300 {
+                world.step(1.0 / 60.0);
+            }
+
+            world.agents().to_vec()
+        };
+
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+    }
+
+    #[test]
+    fn any_ruleset_can_be_simulated() {
+        let ruleset = Ruleset::balanced(7).unwrap();
+        let mut builder = WorldBuilder::new().seed(3).ruleset(ruleset.clone());
+        for species in ruleset.species() {
+            builder = builder.population(species, 10);
+        }
+        let mut world = builder.build().unwrap();
+
+        for _ in 0..60 {
+            world.step(1.0 / 60.0);
+        }
+
+        assert_eq!(world.populations().len(), 7);
+        assert_eq!(world.populations().iter().sum::<usize>(), 70);
+    }
+}
@@ -0,0 +1,251 @@
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context as _, Result};
+
+use crate::{
+    encoding::{Reader, Writer},
+    Arena, BehaviorId, Finish, SimulationState, World,
+};
+
+const MAGIC: &[u8; 4] = b"RPSW";
+
+/// Bumped whenever the layout changes, older snapshots are rejected.
+pub const SNAPSHOT_VERSION: u32 = 7;
+
+impl World {
+    /// Serialises everything the next steps depend on, so a world restored
+    /// with [`World::from_snapshot`] continues exactly like this one as long
+    /// as every agent moves with the default [`ChaseFlee`](crate::ChaseFlee).
+    ///
+    /// Behaviours are code and not part of the snapshot, neither are recorded
+    /// [`Statistics`](crate::Statistics).
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::new());
+        w.0.extend_from_slice(MAGIC);
+        w.u32(SNAPSHOT_VERSION);
+
+        w.f32(self.arena.width);
+        w.f32(self.arena.height);
+        w.boundary(self.arena.boundary);
+        w.f32(self.contact_radius);
+        w.f32(self.jitter);
+        w.stalemate(&self.stalemate);
+        w.obstacles(&self.obstacles);
+        w.u8(self.line_of_sight as u8);
+
+        w.ruleset(&self.ruleset);
+        for steering in &self.steering {
+            w.steering(steering);
+        }
+        w.fight(&self.fight);
+        w.mutation(&self.mutation);
+        w.ecology(self.ecology.as_ref());
+
+        w.u64(self.seed);
+        w.rng(&self.rng);
+        w.u8(match self.state {
+            SimulationState::Play => 0,
+            SimulationState::Pause => 1,
+            SimulationState::Finish => 2,
+        });
+        w.u64(self.tick);
+        w.f64(self.elapsed);
+        w.f64(self.last_conversion);
+        w.option(self.finish, |w, finish| {
+            w.option(finish.winner, |w, species| w.u8(species.0));
+            w.f64(finish.elapsed);
+        });
+
+        w.agents(&self.agents);
+        w.pairs(&self.touching);
+        w.option(self.start.as_ref(), |w, (agents, rng)| {
+            w.agents(agents);
+            w.rng(rng);
+        });
+
+        w.0
+    }
+
+    /// Restores a world saved with [`World::to_snapshot`]. Every species
+    /// moves with [`ChaseFlee`](crate::ChaseFlee) again and no agent keeps
+    /// its own behaviour, add and set them again to carry on with others.
+    pub fn from_snapshot(bytes: &[u8]) -> Result<Self> {
+        let mut r = Reader(bytes);
+        ensure!(r.take(4)? == MAGIC, "not a world snapshot");
+        let version = r.u32()?;
+        ensure!(
+            version == SNAPSHOT_VERSION,
+            "unsupported snapshot version {version}, expected {SNAPSHOT_VERSION}"
+        );
+
+        let (width, height) = (r.f32()?, r.f32()?);
+        let mut world = World::new(width, height, 0);
+        world.arena = Arena::new(width, height, r.boundary()?);
+        world.arena.check()?;
+        world.contact_radius = r.f32()?;
+        ensure!(
+            world.contact_radius.is_finite() && world.contact_radius > 0.0,
+            "the contact radius must be positive, not {}",
+            world.contact_radius
+        );
+        world.jitter = r.f32()?;
+        world.stalemate = r.stalemate()?;
+        world.obstacles = r.obstacles()?;
+        world.line_of_sight = r.u8()? != 0;
+
+        world.ruleset = r.ruleset()?;
+        let len = world.ruleset.len();
+        world.steering = (0..len).map(|_| r.steering()).collect::<Result<_>>()?;
+        world.species_behaviors = vec![BehaviorId::CHASE_FLEE; len];
+        world.fight = r.fight(len)?;
+        world.mutation = r.mutation(len)?;
+        world.ecology = r.ecology()?;
+
+        world.seed = r.u64()?;
+        world.rng = r.rng()?;
+        world.state = match r.u8()? {
+            0 => SimulationState::Play,
+            1 => SimulationState::Pause,
+            2 => SimulationState::Finish,
+            n => bail!("invalid simulation state {n}"),
+        };
+        world.tick = r.u64()?;
+        world.elapsed = r.f64()?;
+        world.last_conversion = r.f64()?;
+        world.finish = r.option(|r| {
+            Ok(Finish {
+                winner: r.option(|r| r.species(len))?,
+                elapsed: r.f64()?,
+            })
+        })?;
+
+        world.agents = r.agents(len)?;
+        world.touching = r.pairs()?;
+        ensure!(
+            world
+                .touching
+                .iter()
+                .all(|&(i, j)| i < j && j < world.agents.len()),
+            "invalid contact"
+        );
+        world.start = r.option(|r| Ok((r.agents(len)?, r.rng()?)))?;
+        ensure!(r.0.is_empty(), "trailing bytes after the snapshot");
+
+        world.rebuild_grid();
+        Ok(world)
+    }
+
+    pub fn save_snapshot<P>(&self, filepath: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        std::fs::write(filepath, self.to_snapshot())
+            .with_context(|| format!("Could not write snapshot {}", filepath.display()))
+    }
+
+    pub fn load_snapshot<P>(filepath: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let filepath = filepath.as_ref();
+        let bytes = std::fs::read(filepath)
+            .with_context(|| format!("Could not read snapshot {}", filepath.display()))?;
+
+        Self::from_snapshot(&bytes)
+            .with_context(|| format!("Invalid snapshot {}", filepath.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Agent, Boundary, RandomWalk, Ruleset, Shape, Species, Wander, WorldBuilder};
+
+    #[test]
+    fn restored_worlds_continue_identically() {
+        let mut world = WorldBuilder::new()
+            .seed(9)
+            .jitter(40.0)
+            .boundary(Boundary::Wrap)
+            .population(Shape::Rock, 30)
+            .population(Shape::Paper, 30)
+            .population(Shape::Scissors, 30)
+            .build()
+            .unwrap();
+        for _ in 0..120 {
+            world.step(1.0 / 60.0);
+        }
+
+        let snapshot = world.to_snapshot();
+        let mut restored = World::from_snapshot(&snapshot).unwrap();
+        assert_eq!(restored.to_snapshot(), snapshot);
+
+        for _ in 0..600 {
+            world.step(1.0 / 60.0);
+            restored.step(1.0 / 60.0);
+        }
+        assert_eq!(restored.agents(), world.agents());
+        assert_eq!(restored.tick(), world.tick());
+    }
+
+    #[test]
+    fn restored_worlds_fall_back_to_chase_flee() {
+        let mut world = WorldBuilder::new()
+            .ruleset(Ruleset::rpsls())
+            .behavior(Species(4), Wander::default())
+            .build()
+            .unwrap();
+        let walk = world.add_behavior(RandomWalk::default());
+        world.spawn(Agent {
+            behavior: Some(walk),
+            ..Agent::new([50.0, 50.0], [0.0, 0.0], Species(3))
+        });
+
+        let restored = World::from_snapshot(&world.to_snapshot()).unwrap();
+        for species in restored.ruleset().species() {
+            assert_eq!(restored.behavior(species), BehaviorId::CHASE_FLEE);
+        }
+        assert_eq!(restored.agents()[0].behavior, None);
+    }
+
+    #[test]
+    fn rejects_damaged_snapshots() {
+        let snapshot = World::new(100.0, 100.0, 0).to_snapshot();
+        let error = |bytes: &[u8]| World::from_snapshot(bytes).unwrap_err().to_string();
+
+        assert_eq!(
+            error(&snapshot[..snapshot.len() - 1]),
+            "unexpected end of data"
+        );
+        assert_eq!(error(b"PNG\0"), "not a world snapshot");
+
+        let mut newer = snapshot.clone();
+        newer[4] = 99;
+        assert_eq!(
+            error(&newer),
+            format!("unsupported snapshot version 99, expected {SNAPSHOT_VERSION}")
+        );
+
+        // the contact radius follows the arena size and boundary
+        let mut flat = snapshot.clone();
+        flat[17..21].copy_from_slice(&0.0_f32.to_le_bytes());
+        assert_eq!(error(&flat), "the contact radius must be positive, not 0");
+
+        // the winner is the only byte telling these two apart
+        let mut world = World::new(100.0, 100.0, 0);
+        let mut finished = |winner| {
+            world.finish = Some(Finish {
+                winner: Some(Species(winner)),
+                elapsed: 1.0,
+            });
+            world.to_snapshot()
+        };
+        let (mut unknown, other) = (finished(2), finished(1));
+        let winner = (0..unknown.len())
+            .find(|&i| unknown[i] != other[i])
+            .unwrap();
+        unknown[winner] = 7;
+        assert_eq!(error(&unknown), "unknown species 7");
+    }
+}